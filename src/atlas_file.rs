@@ -1,20 +1,47 @@
+use crate::constants::mem::ATLAS_BANK;
 use crate::file_utils::ReaderExt;
-use crate::read_write_impl::{Readable, Writeable};
+use crate::read_write_impl::{FileReadable, Readable, Writeable};
 use crate::GameFileError;
 use crate::GameFileError::InvalidAtlas;
 use maikor_platform::constants::{ATLAS_TILE_HEIGHT, ATLAS_TILE_WIDTH};
 
-const ATLAS_SPRITE_SIZE: usize = ATLAS_TILE_HEIGHT * ATLAS_TILE_WIDTH;
+/// Size of a single tile/sprite in bytes
+pub const ATLAS_SPRITE_SIZE: usize = ATLAS_TILE_HEIGHT * ATLAS_TILE_WIDTH;
+/// Number of tiles/sprites that fit in a single atlas bank
+pub const TILES_PER_BANK: usize = ATLAS_BANK / ATLAS_SPRITE_SIZE;
 
+#[derive(Clone, Debug, Eq, PartialEq, Default)]
 pub struct AtlasFile {
     images: Vec<[u8; ATLAS_SPRITE_SIZE]>,
 }
 
 impl AtlasFile {
+    pub fn new(images: Vec<[u8; ATLAS_SPRITE_SIZE]>) -> Self {
+        Self { images }
+    }
+}
+
+impl AtlasFile {
+    pub fn images(&self) -> &[[u8; ATLAS_SPRITE_SIZE]] {
+        &self.images
+    }
+
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
     pub fn validate(&self) -> Result<(), Vec<String>> {
         let mut error = vec![];
-        if self.images.len() > ATLAS_SPRITE_SIZE {
-            error.push(String::from("Atlas has too many images"));
+        if self.images.len() > TILES_PER_BANK {
+            error.push(format!(
+                "Atlas has too many images, max is {} and was {}",
+                TILES_PER_BANK,
+                self.images.len()
+            ));
         }
         if error.is_empty() {
             Ok(())
@@ -65,6 +92,30 @@ impl Readable for AtlasFile {
     }
 }
 
+/// Returns palette index of pixel, pixels are stored two per byte with the left pixel in the
+/// high nibble
+pub(crate) fn get_pixel(tile: &[u8; ATLAS_SPRITE_SIZE], x: usize, y: usize) -> u8 {
+    let byte = tile[y * ATLAS_TILE_WIDTH + x / 2];
+    if x.is_multiple_of(2) {
+        byte >> 4
+    } else {
+        byte & 0x0F
+    }
+}
+
+pub(crate) fn set_pixel(tile: &mut [u8; ATLAS_SPRITE_SIZE], x: usize, y: usize, value: u8) {
+    let byte = &mut tile[y * ATLAS_TILE_WIDTH + x / 2];
+    if x.is_multiple_of(2) {
+        *byte = (*byte & 0x0F) | (value << 4);
+    } else {
+        *byte = (*byte & 0xF0) | (value & 0x0F);
+    }
+}
+
+impl FileReadable for AtlasFile {
+    const CHECK_SIZE: bool = false;
+}
+
 #[cfg(test)]
 mod test {
     use crate::atlas_file::AtlasFile;
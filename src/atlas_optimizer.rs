@@ -0,0 +1,247 @@
+use crate::atlas_file::{get_pixel, set_pixel, AtlasFile, ATLAS_SPRITE_SIZE};
+use maikor_platform::constants::{TILE_HEIGHT, TILE_WIDTH};
+use std::collections::HashMap;
+
+type Tile = [u8; ATLAS_SPRITE_SIZE];
+
+/// Which transformations the optimizer may use when matching tiles
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+pub struct OptimizeOptions {
+    ///Match tiles that are horizontally and/or vertically flipped copies
+    pub flips: bool,
+    ///Match tiles that are rotated copies
+    pub rotations: bool,
+}
+
+impl OptimizeOptions {
+    pub fn new(flips: bool, rotations: bool) -> Self {
+        Self { flips, rotations }
+    }
+
+    /// Only remove exact duplicates
+    pub fn exact() -> Self {
+        Self::new(false, false)
+    }
+
+    /// Use every transformation supported by the hardware
+    pub fn all() -> Self {
+        Self::new(true, true)
+    }
+}
+
+/// Flags needed to draw an original tile using the tile it was merged into
+///
+/// When drawing, `rotated` (90° clockwise) is applied first, then `flip_h` and then `flip_v`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Default)]
+pub struct TileTransform {
+    pub flip_h: bool,
+    pub flip_v: bool,
+    pub rotated: bool,
+}
+
+impl TileTransform {
+    pub fn new(flip_h: bool, flip_v: bool, rotated: bool) -> Self {
+        Self {
+            flip_h,
+            flip_v,
+            rotated,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        !self.flip_h && !self.flip_v && !self.rotated
+    }
+
+    /// Returns the tile that will be drawn when `tile` is drawn with these flags
+    pub fn apply(&self, tile: &Tile) -> Tile {
+        let mut output = *tile;
+        if self.rotated {
+            output = rotate(&output);
+        }
+        if self.flip_h {
+            output = flip_h(&output);
+        }
+        if self.flip_v {
+            output = flip_v(&output);
+        }
+        output
+    }
+
+    fn candidates(options: &OptimizeOptions) -> Vec<TileTransform> {
+        let flips: &[bool] = if options.flips {
+            &[false, true]
+        } else {
+            &[false]
+        };
+        let rotations: &[bool] = if options.rotations {
+            &[false, true]
+        } else {
+            &[false]
+        };
+        let mut output = vec![];
+        for rotated in rotations {
+            for flip_v in flips {
+                for flip_h in flips {
+                    output.push(TileTransform::new(*flip_h, *flip_v, *rotated));
+                }
+            }
+        }
+        output
+    }
+}
+
+/// Where a tile from the original atlas can be found in the optimized atlas
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TileRemap {
+    ///Index of tile in optimized atlas
+    pub index: usize,
+    ///Flags to apply when drawing tile at `index`
+    pub transform: TileTransform,
+}
+
+/// Result of [AtlasFile::optimize]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OptimizedAtlas {
+    ///Atlas with duplicate tiles removed
+    pub atlas: AtlasFile,
+    ///Remapping table, indexed by the tile index in the original atlas
+    pub remap: Vec<TileRemap>,
+}
+
+impl OptimizedAtlas {
+    /// Number of tiles removed by optimization
+    pub fn removed_count(&self) -> usize {
+        self.remap.len() - self.atlas.len()
+    }
+
+    pub fn lookup(&self, old_index: usize) -> Option<TileRemap> {
+        self.remap.get(old_index).copied()
+    }
+}
+
+impl AtlasFile {
+    /// Remove duplicate tiles from this atlas
+    ///
+    /// Tiles keep their original relative order, the first instance of each tile is kept
+    pub fn optimize(&self, options: &OptimizeOptions) -> OptimizedAtlas {
+        let candidates = TileTransform::candidates(options);
+        let mut known: HashMap<Tile, TileRemap> = HashMap::new();
+        let mut images = vec![];
+        let mut remap = vec![];
+        for image in self.images() {
+            if let Some(existing) = known.get(image) {
+                remap.push(*existing);
+                continue;
+            }
+            let index = images.len();
+            images.push(*image);
+            for transform in &candidates {
+                known.entry(transform.apply(image)).or_insert(TileRemap {
+                    index,
+                    transform: *transform,
+                });
+            }
+            remap.push(TileRemap {
+                index,
+                transform: TileTransform::default(),
+            });
+        }
+        OptimizedAtlas {
+            atlas: AtlasFile::new(images),
+            remap,
+        }
+    }
+}
+
+fn map_pixels<F: Fn(usize, usize) -> (usize, usize)>(tile: &Tile, source: F) -> Tile {
+    let mut output = [0; ATLAS_SPRITE_SIZE];
+    for y in 0..TILE_HEIGHT {
+        for x in 0..TILE_WIDTH {
+            let (src_x, src_y) = source(x, y);
+            set_pixel(&mut output, x, y, get_pixel(tile, src_x, src_y));
+        }
+    }
+    output
+}
+
+fn flip_h(tile: &Tile) -> Tile {
+    map_pixels(tile, |x, y| (TILE_WIDTH - 1 - x, y))
+}
+
+fn flip_v(tile: &Tile) -> Tile {
+    map_pixels(tile, |x, y| (x, TILE_HEIGHT - 1 - y))
+}
+
+fn rotate(tile: &Tile) -> Tile {
+    map_pixels(tile, |x, y| (y, TILE_HEIGHT - 1 - x))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn make_tile(seed: u8) -> Tile {
+        let mut tile = [0; ATLAS_SPRITE_SIZE];
+        for (i, byte) in tile.iter_mut().enumerate() {
+            *byte = seed.wrapping_mul(31).wrapping_add(i as u8 * 7);
+        }
+        tile
+    }
+
+    #[test]
+    fn transforms() {
+        let tile = make_tile(3);
+        assert_eq!(flip_h(&flip_h(&tile)), tile);
+        assert_eq!(flip_v(&flip_v(&tile)), tile);
+        assert_eq!(rotate(&rotate(&rotate(&rotate(&tile)))), tile);
+        assert_eq!(rotate(&rotate(&tile)), flip_v(&flip_h(&tile)));
+
+        let mut line = [0; ATLAS_SPRITE_SIZE];
+        line[0] = 0x12;
+        let flipped = flip_h(&line);
+        assert_eq!(flipped[3], 0x21);
+        let rotated = rotate(&line);
+        assert_eq!(get_pixel(&rotated, 7, 0), 1);
+        assert_eq!(get_pixel(&rotated, 7, 1), 2);
+    }
+
+    #[test]
+    fn exact_duplicates() {
+        let a = make_tile(1);
+        let b = make_tile(2);
+        let atlas = AtlasFile::new(vec![a, b, a, a, b]);
+        let result = atlas.optimize(&OptimizeOptions::exact());
+        assert_eq!(result.atlas.images(), &[a, b]);
+        assert_eq!(result.removed_count(), 3);
+        let indexes: Vec<usize> = result.remap.iter().map(|r| r.index).collect();
+        assert_eq!(indexes, vec![0, 1, 0, 0, 1]);
+        assert!(result.remap.iter().all(|r| r.transform.is_identity()));
+    }
+
+    #[test]
+    fn flipped_and_rotated_duplicates() {
+        let a = make_tile(5);
+        let atlas = AtlasFile::new(vec![a, flip_h(&a), flip_v(&a), rotate(&a)]);
+
+        let exact = atlas.optimize(&OptimizeOptions::exact());
+        assert_eq!(exact.atlas.len(), 4);
+
+        let flips = atlas.optimize(&OptimizeOptions::new(true, false));
+        assert_eq!(flips.atlas.len(), 2);
+        assert_eq!(
+            flips.lookup(1),
+            Some(TileRemap {
+                index: 0,
+                transform: TileTransform::new(true, false, false)
+            })
+        );
+        assert_eq!(flips.lookup(3).unwrap().index, 1);
+
+        let all = atlas.optimize(&OptimizeOptions::all());
+        assert_eq!(all.atlas.len(), 1);
+        for (old, remap) in all.remap.iter().enumerate() {
+            let tile = all.atlas.images()[remap.index];
+            assert_eq!(remap.transform.apply(&tile), atlas.images()[old]);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::atlas_file::{AtlasFile, ATLAS_SPRITE_SIZE, TILES_PER_BANK};
+use crate::constants::mem::ATLAS_BANK;
+use crate::file_utils::convert_vec;
+use crate::GameFileError::{InvalidAtlas, TooManyAtlasBanks};
+use crate::{GameFile, GameFileError};
+
+const MAX_BANK_COUNT: usize = u8::MAX as usize;
+
+/// Location of a source atlas inside the packed banks
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AtlasPlacement {
+    ///Index of bank containing the atlas
+    pub bank: u8,
+    ///Index of first tile of the atlas in the bank
+    pub tile_offset: u8,
+    ///Number of tiles in the atlas
+    pub tile_count: u8,
+}
+
+/// Result of [pack_atlases]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PackedAtlases {
+    pub banks: Vec<[u8; ATLAS_BANK]>,
+    ///Placement of each source atlas, in the same order as the source list
+    pub placements: Vec<AtlasPlacement>,
+}
+
+impl PackedAtlases {
+    /// Replace the atlas banks in `game_file` and update the header bank count
+    pub fn apply(self, game_file: &mut GameFile) {
+        game_file.header.atlas_bank_count = self.banks.len() as u8;
+        game_file.atlases = self.banks;
+    }
+}
+
+/// Lay out `atlases` into fixed size banks
+///
+/// Atlases are never split across banks, each atlas is placed in the first bank with enough
+/// free tiles (in source order) so the result is deterministic
+pub fn pack_atlases(atlases: &[AtlasFile]) -> Result<PackedAtlases, GameFileError> {
+    let tile_counts: Vec<usize> = atlases.iter().map(|atlas| atlas.len()).collect();
+    let (placements, bank_count) = plan_atlases(&tile_counts)?;
+    let mut banks = vec![[0; ATLAS_BANK]; bank_count];
+    for (atlas, placement) in atlases.iter().zip(&placements) {
+        let bank = &mut banks[placement.bank as usize];
+        for (j, image) in atlas.images().iter().enumerate() {
+            let start = (placement.tile_offset as usize + j) * ATLAS_SPRITE_SIZE;
+            bank[start..start + ATLAS_SPRITE_SIZE].copy_from_slice(image);
+        }
+    }
+    Ok(PackedAtlases { banks, placements })
+}
+
+/// Work out where [pack_atlases] would place atlases with `tile_counts` tiles, without needing
+/// the atlas content
+///
+/// Returns the placement of each atlas and the number of banks needed
+pub fn plan_atlases(tile_counts: &[usize]) -> Result<(Vec<AtlasPlacement>, usize), GameFileError> {
+    let mut used: Vec<usize> = vec![];
+    let mut placements = vec![];
+    for (i, &tile_count) in tile_counts.iter().enumerate() {
+        if tile_count > TILES_PER_BANK {
+            return Err(InvalidAtlas(format!(
+                "Atlas {} has {} tiles, max per bank is {}",
+                i, tile_count, TILES_PER_BANK
+            )));
+        }
+        let bank = match used
+            .iter()
+            .position(|count| TILES_PER_BANK - count >= tile_count)
+        {
+            Some(idx) => idx,
+            None => {
+                used.push(0);
+                used.len() - 1
+            }
+        };
+        if used.len() > MAX_BANK_COUNT {
+            return Err(TooManyAtlasBanks(used.len()));
+        }
+        placements.push(AtlasPlacement {
+            bank: bank as u8,
+            tile_offset: used[bank] as u8,
+            tile_count: tile_count as u8,
+        });
+        used[bank] += tile_count;
+    }
+    Ok((placements, used.len()))
+}
+
+/// Extract the source atlases from packed banks
+pub fn unpack_atlases(
+    banks: &[[u8; ATLAS_BANK]],
+    placements: &[AtlasPlacement],
+) -> Result<Vec<AtlasFile>, GameFileError> {
+    let mut output = vec![];
+    for (i, placement) in placements.iter().enumerate() {
+        let bank = banks.get(placement.bank as usize).ok_or_else(|| {
+            InvalidAtlas(format!(
+                "Atlas {} is in bank {} but there are only {} banks",
+                i,
+                placement.bank,
+                banks.len()
+            ))
+        })?;
+        let start = placement.tile_offset as usize;
+        let end = start + placement.tile_count as usize;
+        if end > TILES_PER_BANK {
+            return Err(InvalidAtlas(format!(
+                "Atlas {} ends at tile {}, max per bank is {}",
+                i, end, TILES_PER_BANK
+            )));
+        }
+        output.push(AtlasFile::new(
+            (start..end).map(|tile| read_tile(bank, tile)).collect(),
+        ));
+    }
+    Ok(output)
+}
+
+impl AtlasFile {
+    /// Convert a whole atlas bank into an atlas (including any unused tiles)
+    pub fn from_bank(bank: &[u8; ATLAS_BANK]) -> AtlasFile {
+        AtlasFile::new((0..TILES_PER_BANK).map(|i| read_tile(bank, i)).collect())
+    }
+}
+
+fn read_tile(bank: &[u8; ATLAS_BANK], tile: usize) -> [u8; ATLAS_SPRITE_SIZE] {
+    let start = tile * ATLAS_SPRITE_SIZE;
+    convert_vec(bank[start..start + ATLAS_SPRITE_SIZE].to_vec())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::GameFileHeader;
+    use crate::MAIN_CODE;
+
+    fn make_atlas(tiles: usize, value: u8) -> AtlasFile {
+        AtlasFile::new(vec![[value; ATLAS_SPRITE_SIZE]; tiles])
+    }
+
+    #[test]
+    fn pack_and_unpack() {
+        let atlases = vec![
+            make_atlas(100, 1),
+            make_atlas(50, 2),
+            make_atlas(25, 3),
+            make_atlas(10, 4),
+        ];
+        let packed = pack_atlases(&atlases).unwrap();
+        assert_eq!(packed.banks.len(), 2);
+        assert_eq!(
+            packed.placements,
+            vec![
+                AtlasPlacement {
+                    bank: 0,
+                    tile_offset: 0,
+                    tile_count: 100
+                },
+                AtlasPlacement {
+                    bank: 1,
+                    tile_offset: 0,
+                    tile_count: 50
+                },
+                AtlasPlacement {
+                    bank: 0,
+                    tile_offset: 100,
+                    tile_count: 25
+                },
+                AtlasPlacement {
+                    bank: 1,
+                    tile_offset: 50,
+                    tile_count: 10
+                },
+            ]
+        );
+        let unpacked = unpack_atlases(&packed.banks, &packed.placements).unwrap();
+        assert_eq!(unpacked, atlases);
+    }
+
+    #[test]
+    fn too_large() {
+        assert!(pack_atlases(&[make_atlas(TILES_PER_BANK + 1, 1)]).is_err());
+        let atlases = vec![make_atlas(TILES_PER_BANK, 1); 256];
+        assert!(matches!(
+            pack_atlases(&atlases),
+            Err(TooManyAtlasBanks(256))
+        ));
+    }
+
+    #[test]
+    fn apply_to_game_file() {
+        let header = GameFileHeader::new(
+            String::from("a"),
+            1,
+            1,
+            1,
+            0,
+            String::from("a"),
+            String::from("b"),
+            String::from("c"),
+            0,
+            0,
+        );
+        let mut file = GameFile::new(header, [0; MAIN_CODE], vec![], vec![]);
+        let packed = pack_atlases(&[make_atlas(TILES_PER_BANK, 1), make_atlas(1, 2)]).unwrap();
+        packed.apply(&mut file);
+        assert_eq!(file.header.atlas_bank_count, 2);
+        assert_eq!(file.atlases.len(), 2);
+        assert_eq!(AtlasFile::from_bank(&file.atlases[1]).images()[0], [2; 32]);
+    }
+}
@@ -0,0 +1,269 @@
+use maikor_vm_file::manifest_format::ManifestFormat;
+use maikor_vm_file::GameFileError;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "Usage: maikor-file <command> [options]
+
+Commands:
+  info <file>                 Print game file header and bank usage
+  validate <file>             Validate game file or manifest, exits with 1 if invalid
+  build <manifest> -o <file>  Build game file from manifest
+  extract <file> -o <dir>     Extract game file into a project directory
+  verify <manifest> <file>    Rebuild from manifest and compare with game file, exits with 1 if different
+
+Options:
+  -o, --output <path>         Output file
+  --override <key=value>      Override manifest field when building or verifying (can be repeated)
+  --profile <name>            Manifest profile to build, validate or verify, i.e. debug
+  --cache <dir>               Reuse unchanged banks from previous builds
+  --palette <file>            Palette used to write atlases as PNGs when extracting
+  --format <json|toml|ron>    Manifest format when extracting (default json)
+  --json                      Output as JSON
+  -h, --help                  Print this message";
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Command {
+    Info(PathBuf),
+    Validate {
+        file: PathBuf,
+        profile: Option<String>,
+    },
+    Build {
+        manifest: PathBuf,
+        output: PathBuf,
+        overrides: Vec<(String, String)>,
+        profile: Option<String>,
+        cache: Option<PathBuf>,
+    },
+    Extract {
+        file: PathBuf,
+        output: PathBuf,
+        palette: Option<PathBuf>,
+        format: ManifestFormat,
+    },
+    Verify {
+        manifest: PathBuf,
+        file: PathBuf,
+        overrides: Vec<(String, String)>,
+        profile: Option<String>,
+    },
+    Help,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Args {
+    pub command: Command,
+    pub json: bool,
+}
+
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+    let mut positional = vec![];
+    let mut json = false;
+    let mut help = false;
+    let mut output = None;
+    let mut overrides = vec![];
+    let mut palette = None;
+    let mut profile = None;
+    let mut cache = None;
+    let mut format = ManifestFormat::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => help = true,
+            "-o" | "--output" => output = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--cache" => cache = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--profile" => profile = Some(value(&mut args, &arg)?),
+            "--palette" => palette = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--format" => {
+                format = value(&mut args, &arg)?
+                    .parse()
+                    .map_err(|e: GameFileError| e.to_string())?
+            }
+            "--override" => {
+                let text = value(&mut args, &arg)?;
+                let (key, value) = text
+                    .split_once('=')
+                    .ok_or_else(|| format!("Override must be key=value, was {text}"))?;
+                overrides.push((key.trim().to_string(), value.trim().to_string()));
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {arg}")),
+            _ => positional.push(arg),
+        }
+    }
+    if help {
+        return Ok(Args {
+            command: Command::Help,
+            json,
+        });
+    }
+    let mut positional = positional.into_iter();
+    let command = match positional.next().as_deref() {
+        Some("info") => Command::Info(single_path(&mut positional, "info")?),
+        Some("validate") => Command::Validate {
+            file: single_path(&mut positional, "validate")?,
+            profile,
+        },
+        Some("build") => Command::Build {
+            manifest: single_path(&mut positional, "build")?,
+            output: output.ok_or_else(|| String::from("build requires --output"))?,
+            overrides,
+            profile,
+            cache,
+        },
+        Some("extract") => Command::Extract {
+            file: single_path(&mut positional, "extract")?,
+            output: output.ok_or_else(|| String::from("extract requires --output"))?,
+            palette,
+            format,
+        },
+        Some("verify") => {
+            let (manifest, file) = match (positional.next(), positional.next()) {
+                (Some(manifest), Some(file)) => (PathBuf::from(manifest), PathBuf::from(file)),
+                _ => return Err(String::from("verify requires a manifest and a file")),
+            };
+            if let Some(extra) = positional.next() {
+                return Err(format!("Unexpected argument: {extra}"));
+            }
+            Command::Verify {
+                manifest,
+                file,
+                overrides,
+                profile,
+            }
+        }
+        Some("help") => Command::Help,
+        Some(other) => return Err(format!("Unknown command: {other}")),
+        None => return Err(String::from("No command given")),
+    };
+    Ok(Args { command, json })
+}
+
+fn value<I: Iterator<Item = String>>(args: &mut I, name: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("{name} requires a value"))
+}
+
+fn single_path<I: Iterator<Item = String>>(
+    positional: &mut I,
+    command: &str,
+) -> Result<PathBuf, String> {
+    let path = positional
+        .next()
+        .ok_or_else(|| format!("{command} requires a file"))?;
+    if let Some(extra) = positional.next() {
+        return Err(format!("Unexpected argument: {extra}"));
+    }
+    Ok(PathBuf::from(path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(
+            parse(&["info", "game.mkr"]),
+            Ok(Args {
+                command: Command::Info(PathBuf::from("game.mkr")),
+                json: false
+            })
+        );
+        assert_eq!(
+            parse(&["--json", "validate", "game.mkr"]),
+            Ok(Args {
+                command: Command::Validate {
+                    file: PathBuf::from("game.mkr"),
+                    profile: None
+                },
+                json: true
+            })
+        );
+        assert_eq!(parse(&["info", "-h"]).unwrap().command, Command::Help);
+        assert_eq!(
+            parse(&[
+                "build",
+                "manifest.json",
+                "-o",
+                "game.mkr",
+                "--override",
+                "build=12",
+                "--override",
+                "version = 1.0",
+                "--profile",
+                "debug"
+            ])
+            .unwrap()
+            .command,
+            Command::Build {
+                manifest: PathBuf::from("manifest.json"),
+                output: PathBuf::from("game.mkr"),
+                overrides: vec![
+                    (String::from("build"), String::from("12")),
+                    (String::from("version"), String::from("1.0"))
+                ],
+                profile: Some(String::from("debug")),
+                cache: None
+            }
+        );
+        assert_eq!(
+            parse(&[
+                "extract",
+                "game.mkr",
+                "-o",
+                "out",
+                "--palette",
+                "a.gpl",
+                "--format",
+                "toml"
+            ])
+            .unwrap()
+            .command,
+            Command::Extract {
+                file: PathBuf::from("game.mkr"),
+                output: PathBuf::from("out"),
+                palette: Some(PathBuf::from("a.gpl")),
+                format: ManifestFormat::Toml
+            }
+        );
+        assert_eq!(
+            parse(&[
+                "verify",
+                "maikor.toml",
+                "game.mkr",
+                "--profile",
+                "release",
+                "--override",
+                "build=12"
+            ])
+            .unwrap()
+            .command,
+            Command::Verify {
+                manifest: PathBuf::from("maikor.toml"),
+                file: PathBuf::from("game.mkr"),
+                overrides: vec![(String::from("build"), String::from("12"))],
+                profile: Some(String::from("release"))
+            }
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["info"]).is_err());
+        assert!(parse(&["info", "a", "b"]).is_err());
+        assert!(parse(&["run", "a"]).is_err());
+        assert!(parse(&["info", "a", "--fast"]).is_err());
+        assert!(parse(&["build", "a"]).is_err());
+        assert!(parse(&["build", "a", "-o"]).is_err());
+        assert!(parse(&["build", "a", "-o", "b", "--override", "build"]).is_err());
+        assert!(parse(&["extract", "a", "-o", "b", "--format", "yaml"]).is_err());
+        assert!(parse(&["verify", "a"]).is_err());
+        assert!(parse(&["verify", "a", "b", "c"]).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+use maikor_vm_file::atlas_file::TILES_PER_BANK;
+use maikor_vm_file::build::{build_from_manifest, build_from_manifest_cached, verify_build};
+use maikor_vm_file::build_cache::{BankStatus, BuildCache};
+use maikor_vm_file::constants::mem::{CODE_BANK, MAIN_CODE};
+use maikor_vm_file::manifest::Manifest;
+use maikor_vm_file::read_write_impl::{get_file_size, FileReadable};
+use maikor_vm_file::GameFileError::ValidationFailed;
+use maikor_vm_file::{GameFile, GameFileError, MAX_FILE_SIZE};
+use serde_json::json;
+use std::path::Path;
+
+/// Build game file from manifest and print summary
+pub fn build(
+    manifest_path: &Path,
+    output: &Path,
+    overrides: &[(String, String)],
+    profile: Option<&str>,
+    cache_dir: Option<&Path>,
+    json: bool,
+) -> Result<bool, GameFileError> {
+    let mut manifest = Manifest::from_file(manifest_path)?.resolve_env(profile)?;
+    for (key, value) in overrides {
+        manifest.apply_override(key, value)?;
+    }
+    let base_dir = manifest_path.parent().unwrap_or_else(|| Path::new(""));
+    manifest
+        .validate(base_dir)
+        .map_err(|errors| ValidationFailed(errors.join("\n")))?;
+    let result = match cache_dir {
+        Some(dir) => {
+            let mut cache = BuildCache::open(dir)?;
+            let result = build_from_manifest_cached(&manifest, base_dir, &mut cache)?;
+            cache.save()?;
+            result
+        }
+        None => build_from_manifest(&manifest, base_dir)?,
+    };
+    result.game_file.write(output)?;
+
+    let file = &result.game_file;
+    let report = &result.report;
+    let size = get_file_size(output);
+    let mut atlas_usage = vec![0; file.atlases.len()];
+    for placement in &report.atlas_placements {
+        atlas_usage[placement.bank as usize] += placement.tile_count as usize;
+    }
+
+    if json {
+        let output = json!({
+            "file": output.display().to_string(),
+            "size": size,
+            "max_size": MAX_FILE_SIZE,
+            "header": file.header,
+            "main_code": report.main_code_size,
+            "code_banks": report.code_bank_sizes,
+            "atlas_banks": atlas_usage,
+            "file_expansions": report.file_expansions,
+            "cache": result.cache,
+        });
+        println!("{output:#}");
+    } else {
+        println!(
+            "Built {} ({} build {})",
+            output.display(),
+            file.header.id,
+            file.header.build
+        );
+        println!("Size:        {size} bytes (max {MAX_FILE_SIZE})");
+        for expansion in &report.file_expansions {
+            println!(
+                "{} '{}' matched {} files: {}",
+                expansion.field,
+                expansion.entry,
+                expansion.files.len(),
+                expansion.files.join(", ")
+            );
+        }
+        if let Some(cache) = &result.cache {
+            println!(
+                "Cache:       {} of {} atlas banks rebuilt",
+                cache.rebuilt_count(),
+                cache.atlas_banks.len()
+            );
+        }
+        let status = |statuses: Option<&Vec<BankStatus>>, i: usize| match statuses
+            .and_then(|statuses| statuses.get(i))
+        {
+            Some(BankStatus::Rebuilt(reasons)) => format!(
+                " (rebuilt: {})",
+                reasons
+                    .iter()
+                    .map(|reason| reason.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            _ => String::new(),
+        };
+        let cache = result.cache.as_ref();
+        println!("Main code:   {}/{} bytes", report.main_code_size, MAIN_CODE);
+        println!("Code banks:  {}/255", file.code_banks.len());
+        for (i, size) in report.code_bank_sizes.iter().enumerate() {
+            println!("  {i:>3}: {size}/{CODE_BANK} bytes");
+        }
+        println!("Atlas banks: {}/255", file.atlases.len());
+        for (i, tiles) in atlas_usage.iter().enumerate() {
+            let status = status(cache.map(|cache| &cache.atlas_banks), i);
+            println!("  {i:>3}: {tiles}/{TILES_PER_BANK} tiles{status}");
+        }
+    }
+    Ok(true)
+}
+
+/// Rebuild game file from manifest and compare, returns false if they're different
+///
+/// `overrides` and `profile` must be the same as when the file was built
+pub fn verify(
+    manifest_path: &Path,
+    file_path: &Path,
+    overrides: &[(String, String)],
+    profile: Option<&str>,
+    json: bool,
+) -> Result<bool, GameFileError> {
+    let mut manifest = Manifest::from_file(manifest_path)?.resolve_env(profile)?;
+    for (key, value) in overrides {
+        manifest.apply_override(key, value)?;
+    }
+    let base_dir = manifest_path.parent().unwrap_or_else(|| Path::new(""));
+    let file = GameFile::read(file_path)?;
+    let verification = verify_build(&manifest, base_dir, &file)?;
+    if json {
+        let output = json!({
+            "file": file_path.display().to_string(),
+            "manifest": manifest_path.display().to_string(),
+            "build_info": file.build_info.is_some(),
+            "matches": verification.matches,
+            "differences": verification.differences,
+        });
+        println!("{output:#}");
+    } else if verification.matches {
+        println!(
+            "{} matches {}",
+            file_path.display(),
+            manifest_path.display()
+        );
+    } else {
+        println!(
+            "{} doesn't match {}:",
+            file_path.display(),
+            manifest_path.display()
+        );
+        for difference in &verification.differences {
+            println!("  {difference}");
+        }
+        if file.build_info.is_none() {
+            println!(
+                "  (file has no build info, enable build_info in the manifest to check inputs)"
+            );
+        }
+    }
+    Ok(verification.matches)
+}
@@ -0,0 +1,51 @@
+use maikor_vm_file::extract::{extract_project, ExtractOptions};
+use maikor_vm_file::manifest_format::ManifestFormat;
+use maikor_vm_file::palette::Palette;
+use maikor_vm_file::palette_format::PaletteImportOptions;
+use maikor_vm_file::read_write_impl::FileReadable;
+use maikor_vm_file::{GameFile, GameFileError};
+use serde_json::json;
+use std::path::Path;
+
+/// Extract game file into project directory
+pub fn extract(
+    path: &Path,
+    output: &Path,
+    palette: Option<&Path>,
+    manifest_format: ManifestFormat,
+    json: bool,
+) -> Result<bool, GameFileError> {
+    let file = GameFile::read(path)?;
+    let png_palette = match palette {
+        Some(palette) => Some(Palette::import_file(
+            palette,
+            &PaletteImportOptions::default(),
+        )?),
+        None => None,
+    };
+    let manifest = extract_project(
+        &file,
+        output,
+        &ExtractOptions {
+            png_palette,
+            manifest_format,
+        },
+    )?;
+    if json {
+        let output = json!({
+            "dir": output.display().to_string(),
+            "manifest": manifest,
+        });
+        println!("{output:#}");
+    } else {
+        println!(
+            "Extracted {} to {} ({} code files, {} atlas files, {} palettes)",
+            path.display(),
+            output.display(),
+            manifest.code_files.len(),
+            manifest.atlas_files.len(),
+            manifest.palette_files.len()
+        );
+    }
+    Ok(true)
+}
@@ -0,0 +1,110 @@
+use maikor_vm_file::constants::mem::{ATLAS_BANK, CODE_BANK, MAIN_CODE};
+use maikor_vm_file::manifest::Manifest;
+use maikor_vm_file::manifest_format::ManifestFormat;
+use maikor_vm_file::read_write_impl::{get_file_size, validate_file, FileReadable};
+use maikor_vm_file::{GameFile, GameFileError, MAX_FILE_SIZE, MIN_FILE_SIZE};
+use serde_json::json;
+use std::path::Path;
+
+/// Print header and bank usage
+pub fn info(path: &Path, json: bool) -> Result<bool, GameFileError> {
+    let size = get_file_size(path);
+    let file = GameFile::read(path)?;
+    let header = &file.header;
+    let palettes: Vec<&str> = file
+        .palettes
+        .iter()
+        .flat_map(|set| set.palettes().iter().map(|named| named.name.as_str()))
+        .collect();
+    if json {
+        let output = json!({
+            "file": path.display().to_string(),
+            "size": size,
+            "min_size": MIN_FILE_SIZE,
+            "max_size": MAX_FILE_SIZE,
+            "header": header,
+            "compatibility": header.platform_compatibility(),
+            "code_banks": file.code_banks.len(),
+            "atlas_banks": file.atlases.len(),
+            "palettes": palettes,
+            "build_info": file.build_info,
+        });
+        println!("{output:#}");
+    } else {
+        println!("File:           {}", path.display());
+        println!("Size:           {size} bytes (min {MIN_FILE_SIZE}, max {MAX_FILE_SIZE})");
+        println!("ID:             {}", header.id);
+        println!("Name:           {}", header.name);
+        println!("Version:        {}", header.version);
+        println!("Author:         {}", header.author);
+        println!("Build:          {}", header.build);
+        println!(
+            "Maikor version: compiled for {}, min {} ({})",
+            header.compiled_for_version(),
+            header.min_version(),
+            header.platform_compatibility()
+        );
+        println!("RAM banks:      {}", header.ram_bank_count);
+        println!("Main code:      {MAIN_CODE} bytes");
+        println!(
+            "Code banks:     {} ({} bytes)",
+            file.code_banks.len(),
+            file.code_banks.len() * CODE_BANK
+        );
+        println!(
+            "Atlas banks:    {} ({} bytes)",
+            file.atlases.len(),
+            file.atlases.len() * ATLAS_BANK
+        );
+        if !palettes.is_empty() {
+            println!("Palettes:       {}", palettes.join(", "));
+        }
+        if let Some(build_info) = &file.build_info {
+            println!("Built with:     {}", build_info.tool);
+            println!("Built at:       {} (Unix time)", build_info.timestamp);
+            println!("Manifest hash:  {}", build_info.manifest_hash);
+            println!("Input files:    {}", build_info.inputs.len());
+            for input in &build_info.inputs {
+                println!("  {} {}", input.hash, input.file);
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Run all validation on a game file or manifest (`.json`, `.toml` or `.ron`), returns false if the file is invalid
+pub fn validate(path: &Path, profile: Option<&str>, json: bool) -> Result<bool, GameFileError> {
+    let errors = match collect_errors(path, profile) {
+        Ok(_) => vec![],
+        Err(errors) => errors,
+    };
+    if json {
+        let output = json!({
+            "file": path.display().to_string(),
+            "valid": errors.is_empty(),
+            "errors": errors,
+        });
+        println!("{output:#}");
+    } else if errors.is_empty() {
+        println!("{} is valid", path.display());
+    } else {
+        println!("{} is invalid:", path.display());
+        for error in &errors {
+            println!("  {error}");
+        }
+    }
+    Ok(errors.is_empty())
+}
+
+fn collect_errors(path: &Path, profile: Option<&str>) -> Result<(), Vec<String>> {
+    if ManifestFormat::from_path(path).is_some() {
+        let manifest = Manifest::from_file(path)
+            .and_then(|manifest| manifest.resolve_env(profile))
+            .map_err(|e| vec![e.to_string()])?;
+        return manifest.validate(path.parent().unwrap_or_else(|| Path::new("")));
+    }
+    validate_file(path, true).map_err(|e| vec![e.to_string()])?;
+    let file = GameFile::read(path).map_err(|e| vec![e.to_string()])?;
+    file.validate()
+        .map_err(|text| text.lines().map(String::from).collect())
+}
@@ -0,0 +1,60 @@
+mod args;
+mod build;
+mod extract;
+mod info;
+
+use crate::args::{parse_args, Command, USAGE};
+use std::env;
+use std::process::exit;
+
+fn main() {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{message}\n\n{USAGE}");
+            exit(2);
+        }
+    };
+    let result = match args.command {
+        Command::Info(path) => info::info(&path, args.json),
+        Command::Validate { file, profile } => info::validate(&file, profile.as_deref(), args.json),
+        Command::Build {
+            manifest,
+            output,
+            overrides,
+            profile,
+            cache,
+        } => build::build(
+            &manifest,
+            &output,
+            &overrides,
+            profile.as_deref(),
+            cache.as_deref(),
+            args.json,
+        ),
+        Command::Extract {
+            file,
+            output,
+            palette,
+            format,
+        } => extract::extract(&file, &output, palette.as_deref(), format, args.json),
+        Command::Verify {
+            manifest,
+            file,
+            overrides,
+            profile,
+        } => build::verify(&manifest, &file, &overrides, profile.as_deref(), args.json),
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(true)
+        }
+    };
+    match result {
+        Ok(true) => {}
+        Ok(false) => exit(1),
+        Err(e) => {
+            eprintln!("{e}");
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use crate::checksum::crc32;
+use crate::read_write_impl::Writeable;
+use crate::GameFileError::{InvalidPatch, PatchNotApplicable};
+use crate::{GameFile, GameFileError, MAX_FILE_SIZE};
+
+const BPS_HEADER: &[u8; 4] = b"BPS1";
+///Source CRC, target CRC and patch CRC
+const FOOTER_LEN: usize = 12;
+
+const SOURCE_READ: u64 = 0;
+const TARGET_READ: u64 = 1;
+const SOURCE_COPY: u64 = 2;
+const TARGET_COPY: u64 = 3;
+
+/// Create a BPS patch that converts `source` into `target`
+///
+/// Only `SourceRead` and `TargetRead` actions are used, so moved data is stored in full
+pub fn create_bps(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut output = BPS_HEADER.to_vec();
+    write_number(&mut output, source.len() as u64);
+    write_number(&mut output, target.len() as u64);
+    //no metadata
+    write_number(&mut output, 0);
+
+    let mut i = 0;
+    while i < target.len() {
+        let start = i;
+        if source.get(i) == Some(&target[i]) {
+            while i < target.len() && source.get(i) == Some(&target[i]) {
+                i += 1;
+            }
+            write_number(&mut output, ((i - start - 1) as u64) << 2 | SOURCE_READ);
+        } else {
+            while i < target.len() && source.get(i) != Some(&target[i]) {
+                i += 1;
+            }
+            write_number(&mut output, ((i - start - 1) as u64) << 2 | TARGET_READ);
+            output.extend_from_slice(&target[start..i]);
+        }
+    }
+
+    output.extend_from_slice(&crc32(source).to_le_bytes());
+    output.extend_from_slice(&crc32(target).to_le_bytes());
+    let patch_crc = crc32(&output);
+    output.extend_from_slice(&patch_crc.to_le_bytes());
+    output
+}
+
+/// Apply BPS `patch` to `source`, all checksums are verified
+pub fn apply_bps(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, GameFileError> {
+    if !patch.starts_with(BPS_HEADER) || patch.len() < BPS_HEADER.len() + FOOTER_LEN {
+        return Err(InvalidPatch(String::from("Not a BPS patch")));
+    }
+    let body_end = patch.len() - FOOTER_LEN;
+    let footer = &patch[body_end..];
+    let read_crc = |idx: usize| {
+        u32::from_le_bytes([
+            footer[idx * 4],
+            footer[idx * 4 + 1],
+            footer[idx * 4 + 2],
+            footer[idx * 4 + 3],
+        ])
+    };
+    if crc32(&patch[..patch.len() - 4]) != read_crc(2) {
+        return Err(InvalidPatch(String::from("BPS patch checksum mismatch")));
+    }
+    if crc32(source) != read_crc(0) {
+        return Err(PatchNotApplicable(String::from(
+            "file content doesn't match patch source",
+        )));
+    }
+
+    let mut reader = PatchReader {
+        bytes: &patch[..body_end],
+        pos: BPS_HEADER.len(),
+    };
+    let source_size = reader.number()? as usize;
+    let target_size = reader.number()?;
+    if target_size > MAX_FILE_SIZE {
+        return Err(InvalidPatch(format!(
+            "BPS target is {target_size} bytes, max is {MAX_FILE_SIZE}"
+        )));
+    }
+    let target_size = target_size as usize;
+    let metadata_size = reader.number()?;
+    if metadata_size > reader.remaining() as u64 {
+        return Err(InvalidPatch(format!(
+            "BPS metadata is {metadata_size} bytes, patch only has {} left",
+            reader.remaining()
+        )));
+    }
+    reader.take(metadata_size as usize)?;
+    if source_size != source.len() {
+        return Err(PatchNotApplicable(format!(
+            "patch is for a {} byte file but file is {} bytes",
+            source_size,
+            source.len()
+        )));
+    }
+
+    let mut output: Vec<u8> = Vec::with_capacity(target_size);
+    let mut source_offset: i64 = 0;
+    let mut target_offset: i64 = 0;
+    while reader.pos < reader.bytes.len() {
+        let data = reader.number()?;
+        let len = (data >> 2) as usize + 1;
+        if len > target_size - output.len() {
+            return Err(InvalidPatch(String::from(
+                "BPS patch writes past target size",
+            )));
+        }
+        match data & 3 {
+            SOURCE_READ => {
+                let bytes = output
+                    .len()
+                    .checked_add(len)
+                    .and_then(|end| source.get(output.len()..end))
+                    .ok_or_else(|| InvalidPatch(String::from("BPS read past end of source")))?;
+                output.extend_from_slice(bytes);
+            }
+            TARGET_READ => output.extend_from_slice(reader.take(len)?),
+            SOURCE_COPY => {
+                source_offset = add_offset(source_offset, reader.offset()?)?;
+                let start = usize::try_from(source_offset)
+                    .map_err(|_| InvalidPatch(String::from("BPS copy before start of source")))?;
+                let bytes = start
+                    .checked_add(len)
+                    .and_then(|end| source.get(start..end))
+                    .ok_or_else(|| InvalidPatch(String::from("BPS copy past end of source")))?;
+                output.extend_from_slice(bytes);
+                source_offset = add_offset(source_offset, len as i64)?;
+            }
+            TARGET_COPY => {
+                target_offset = add_offset(target_offset, reader.offset()?)?;
+                let start = usize::try_from(target_offset)
+                    .map_err(|_| InvalidPatch(String::from("BPS copy before start of target")))?;
+                let end = start
+                    .checked_add(len)
+                    .ok_or_else(|| InvalidPatch(String::from("BPS copy past end of target")))?;
+                //target copies can overlap the bytes being written so copy one at a time
+                for i in start..end {
+                    let byte = *output
+                        .get(i)
+                        .ok_or_else(|| InvalidPatch(String::from("BPS copy past end of target")))?;
+                    output.push(byte);
+                }
+                target_offset = add_offset(target_offset, len as i64)?;
+            }
+            _ => unreachable!("action is two bits"),
+        }
+    }
+    if output.len() != target_size || crc32(&output) != read_crc(1) {
+        return Err(InvalidPatch(String::from(
+            "patched content doesn't match patch target",
+        )));
+    }
+    Ok(output)
+}
+
+fn add_offset(offset: i64, change: i64) -> Result<i64, GameFileError> {
+    offset
+        .checked_add(change)
+        .ok_or_else(|| InvalidPatch(String::from("BPS copy offset is out of range")))
+}
+
+struct PatchReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], GameFileError> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(self.pos..end))
+            .ok_or_else(|| InvalidPatch(String::from("BPS patch is truncated")))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn number(&mut self) -> Result<u64, GameFileError> {
+        let mut data: u64 = 0;
+        let mut shift: u64 = 1;
+        loop {
+            let byte = self.take(1)?[0];
+            data = (byte as u64 & 0x7F)
+                .checked_mul(shift)
+                .and_then(|value| value.checked_add(data))
+                .ok_or_else(|| InvalidPatch(String::from("BPS number is too large")))?;
+            if byte & 0x80 != 0 {
+                return Ok(data);
+            }
+            shift = shift
+                .checked_shl(7)
+                .filter(|value| *value != 0)
+                .ok_or_else(|| InvalidPatch(String::from("BPS number is too large")))?;
+            data = data
+                .checked_add(shift)
+                .ok_or_else(|| InvalidPatch(String::from("BPS number is too large")))?;
+        }
+    }
+
+    fn offset(&mut self) -> Result<i64, GameFileError> {
+        let data = self.number()?;
+        let value = (data >> 1) as i64;
+        Ok(if data & 1 == 1 { -value } else { value })
+    }
+}
+
+fn write_number(output: &mut Vec<u8>, mut data: u64) {
+    loop {
+        let x = (data & 0x7F) as u8;
+        data >>= 7;
+        if data == 0 {
+            output.push(0x80 | x);
+            break;
+        }
+        output.push(x);
+        data -= 1;
+    }
+}
+
+impl GameFile {
+    /// Create BPS patch that converts this file into `target`
+    pub fn create_bps(&self, target: &GameFile) -> Result<Vec<u8>, GameFileError> {
+        Ok(create_bps(&self.as_bytes()?, &target.as_bytes()?))
+    }
+
+    /// Apply BPS patch to this file, the result is parsed and validated
+    pub fn apply_bps(&self, patch: &[u8]) -> Result<GameFile, GameFileError> {
+        GameFile::from_patched_bytes(&apply_bps(&self.as_bytes()?, patch)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constants::mem::{ATLAS_BANK, CODE_BANK, MAIN_CODE};
+    use crate::GameFileError::ValidationFailed;
+    use crate::GameFileHeader;
+
+    #[test]
+    fn numbers() {
+        for value in [
+            0,
+            1,
+            127,
+            128,
+            255,
+            16511,
+            16512,
+            1_000_000,
+            u32::MAX as u64,
+        ] {
+            let mut bytes = vec![];
+            write_number(&mut bytes, value);
+            let mut reader = PatchReader {
+                bytes: &bytes,
+                pos: 0,
+            };
+            assert_eq!(reader.number().unwrap(), value);
+            assert_eq!(reader.pos, bytes.len());
+        }
+    }
+
+    #[test]
+    fn copy_actions() {
+        let source = b"abcdef";
+        let mut patch = BPS_HEADER.to_vec();
+        write_number(&mut patch, 6);
+        write_number(&mut patch, 9);
+        write_number(&mut patch, 0);
+        //copy "def" from source offset 3
+        write_number(&mut patch, (2 << 2) | SOURCE_COPY);
+        write_number(&mut patch, 3 << 1);
+        //write "x"
+        write_number(&mut patch, TARGET_READ);
+        patch.push(b'x');
+        //repeat "dex" from target offset 1... "efx" then "d" → overlapping copy
+        write_number(&mut patch, (4 << 2) | TARGET_COPY);
+        write_number(&mut patch, 1 << 1);
+        let target = b"defxefxef";
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        let crc = crc32(&patch);
+        patch.extend_from_slice(&crc.to_le_bytes());
+        assert_eq!(apply_bps(source, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn target_too_large() {
+        let source = b"abc";
+        let mut patch = BPS_HEADER.to_vec();
+        write_number(&mut patch, 3);
+        write_number(&mut patch, u64::MAX >> 8);
+        write_number(&mut patch, 0);
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        let crc = crc32(&patch);
+        patch.extend_from_slice(&crc.to_le_bytes());
+        assert!(matches!(apply_bps(source, &patch), Err(InvalidPatch(_))));
+    }
+
+    fn with_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        let crc = crc32(&patch);
+        patch.extend_from_slice(&crc.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn metadata_too_large() {
+        let source = b"abc";
+        let mut patch = BPS_HEADER.to_vec();
+        write_number(&mut patch, 3);
+        write_number(&mut patch, 3);
+        write_number(&mut patch, u64::MAX);
+        let patch = with_footer(patch, source, source);
+        assert!(matches!(apply_bps(source, &patch), Err(InvalidPatch(_))));
+    }
+
+    #[test]
+    fn copy_offset_overflow() {
+        let source = b"ab";
+        let mut patch = BPS_HEADER.to_vec();
+        write_number(&mut patch, 2);
+        write_number(&mut patch, 2);
+        write_number(&mut patch, 0);
+        write_number(&mut patch, SOURCE_COPY);
+        write_number(&mut patch, 0);
+        write_number(&mut patch, SOURCE_COPY);
+        write_number(&mut patch, (i64::MAX as u64) << 1);
+        let patch = with_footer(patch, source, source);
+        assert!(matches!(apply_bps(source, &patch), Err(InvalidPatch(_))));
+
+        let mut patch = BPS_HEADER.to_vec();
+        write_number(&mut patch, 2);
+        write_number(&mut patch, 3);
+        write_number(&mut patch, 0);
+        write_number(&mut patch, SOURCE_READ);
+        write_number(&mut patch, TARGET_COPY);
+        write_number(&mut patch, 0);
+        write_number(&mut patch, TARGET_COPY);
+        write_number(&mut patch, (i64::MAX as u64) << 1);
+        let patch = with_footer(patch, source, b"aaa");
+        assert!(matches!(apply_bps(source, &patch), Err(InvalidPatch(_))));
+    }
+
+    #[test]
+    fn round_trip() {
+        let source: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        let mut target = source.clone();
+        target[0] = 99;
+        target[50..70].fill(1);
+        target.truncate(180);
+        let patch = create_bps(&source, &target);
+        assert_eq!(apply_bps(&source, &patch).unwrap(), target);
+        assert!(matches!(
+            apply_bps(&target, &patch),
+            Err(PatchNotApplicable(_))
+        ));
+        let mut corrupt = patch.clone();
+        corrupt[8] ^= 1;
+        assert!(matches!(apply_bps(&source, &corrupt), Err(InvalidPatch(_))));
+    }
+
+    #[test]
+    fn game_file() {
+        let header = GameFileHeader::new(
+            String::from("com.example.game"),
+            1,
+            1,
+            1,
+            0,
+            String::from("Game"),
+            String::from("1.0"),
+            String::from("Author"),
+            1,
+            1,
+        );
+        let source = GameFile::new(
+            header,
+            [1; MAIN_CODE],
+            vec![[2; CODE_BANK]],
+            vec![[3; ATLAS_BANK]],
+        );
+        let mut target = GameFile::new(
+            source.header.clone(),
+            source.main_code,
+            source.code_banks.clone(),
+            source.atlases.clone(),
+        );
+        target.header.name = String::from("Modded Game");
+        target.atlases[0][10] = 0;
+        let patch = source.create_bps(&target).unwrap();
+        let patched = source.apply_bps(&patch).unwrap();
+        assert_eq!(patched.header.name, "Modded Game");
+        assert_eq!(patched.as_bytes().unwrap(), target.as_bytes().unwrap());
+
+        target.header.atlas_bank_count = 0;
+        target.atlases.clear();
+        let patch = source.create_bps(&target).unwrap();
+        assert!(matches!(source.apply_bps(&patch), Err(ValidationFailed(_))));
+    }
+}
@@ -0,0 +1,462 @@
+use crate::atlas_file::AtlasFile;
+use crate::atlas_packer::{pack_atlases, AtlasPlacement};
+use crate::build_cache::{BuildCache, CacheReport};
+use crate::build_info::{build_timestamp, BuildInfo};
+use crate::constants::mem::{CODE_BANK, MAIN_CODE};
+use crate::file_utils::convert_vec;
+use crate::icon::GameImage;
+use crate::manifest::Manifest;
+use crate::manifest_files::FileExpansion;
+use crate::palette_set::PaletteSet;
+use crate::read_write_impl::{validate_file, FileReadable, Writeable};
+use crate::section::{read_sections, SectionId};
+use crate::GameFileError::{FileAccessError, InvalidCode, ManifestParsingError, ValidationFailed};
+use crate::{GameFile, GameFileError};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+const MAX_BANK_COUNT: usize = u8::MAX as usize;
+
+/// Details about how the game file content was laid out
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BuildReport {
+    ///Size of main code file (before padding)
+    pub main_code_size: usize,
+    ///Size of each code file (before padding)
+    pub code_bank_sizes: Vec<usize>,
+    ///Location of each atlas file in the atlas banks
+    pub atlas_placements: Vec<AtlasPlacement>,
+    ///Patterns and directories in the manifest file lists and the files used for them
+    pub file_expansions: Vec<FileExpansion>,
+}
+
+pub struct BuildOutput {
+    pub game_file: GameFile,
+    pub report: BuildReport,
+    ///Which atlas banks were reused, only set for [build_from_manifest_cached]
+    pub cache: Option<CacheReport>,
+}
+
+/// Assemble the files referenced by `manifest` into a validated [GameFile]
+///
+/// Relative paths in the manifest are resolved against `base_dir` (normally the directory
+/// containing the manifest), patterns and directories in file lists are expanded with
+/// [Manifest::expand_files]
+pub fn build_from_manifest<P: AsRef<Path>>(
+    manifest: &Manifest,
+    base_dir: P,
+) -> Result<BuildOutput, GameFileError> {
+    build(manifest, base_dir.as_ref(), None, None)
+}
+
+/// Same as [build_from_manifest] but atlas banks whose source files haven't changed since the
+/// previous build are reused from `cache`, the game file is identical either way
+///
+/// `cache` is updated with this build, call [BuildCache::save] to keep it for the next build
+pub fn build_from_manifest_cached<P: AsRef<Path>>(
+    manifest: &Manifest,
+    base_dir: P,
+    cache: &mut BuildCache,
+) -> Result<BuildOutput, GameFileError> {
+    build(manifest, base_dir.as_ref(), Some(cache), None)
+}
+
+/// Result of [verify_build]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Verification {
+    ///True if the rebuilt file is byte-for-byte identical
+    pub matches: bool,
+    ///Each part of the file that is different, and any changed inputs if both files have build info
+    pub differences: Vec<String>,
+}
+
+/// Rebuild `manifest` and compare the result with `game_file`
+///
+/// If `game_file` has build info its timestamp is reused, so a file built with
+/// [Manifest::build_info] can be reproduced exactly
+pub fn verify_build<P: AsRef<Path>>(
+    manifest: &Manifest,
+    base_dir: P,
+    game_file: &GameFile,
+) -> Result<Verification, GameFileError> {
+    let timestamp = game_file.build_info.as_ref().map(|info| info.timestamp);
+    let rebuilt = build(manifest, base_dir.as_ref(), None, timestamp)?.game_file;
+    let mut differences = vec![];
+    if let (Some(existing), Some(rebuilt)) = (&game_file.build_info, &rebuilt.build_info) {
+        differences.extend(existing.differences(rebuilt));
+    }
+
+    let header = serde_json::to_value(&game_file.header).expect("header is valid JSON");
+    let rebuilt_header = serde_json::to_value(&rebuilt.header).expect("header is valid JSON");
+    if let (Some(header), Some(rebuilt_header)) = (header.as_object(), rebuilt_header.as_object()) {
+        for (field, value) in header {
+            if rebuilt_header.get(field) != Some(value) {
+                differences.push(format!(
+                    "Header {field} is {value} but rebuilt is {}",
+                    rebuilt_header[field]
+                ));
+            }
+        }
+    }
+    if game_file.main_code != rebuilt.main_code {
+        differences.push(String::from("Main code differs"));
+    }
+    compare_banks(
+        "Code",
+        &game_file.code_banks,
+        &rebuilt.code_banks,
+        &mut differences,
+    );
+    compare_banks(
+        "Atlas",
+        &game_file.atlases,
+        &rebuilt.atlases,
+        &mut differences,
+    );
+
+    let sections = sections_by_id(game_file)?;
+    let rebuilt_sections = sections_by_id(&rebuilt)?;
+    let ids: BTreeSet<&u8> = sections.keys().chain(rebuilt_sections.keys()).collect();
+    for id in ids {
+        let name = match SectionId::from_u8(*id) {
+            Some(section) => format!("{section:?}"),
+            None => format!("Unknown ({id})"),
+        };
+        match (sections.get(id), rebuilt_sections.get(id)) {
+            (Some(_), None) => differences.push(format!("{name} section isn't in rebuilt file")),
+            (None, Some(_)) => differences.push(format!("{name} section is only in rebuilt file")),
+            (Some(data), Some(rebuilt_data)) if data != rebuilt_data => {
+                differences.push(format!("{name} section differs"))
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Verification {
+        matches: game_file.as_bytes()? == rebuilt.as_bytes()?,
+        differences,
+    })
+}
+
+fn compare_banks<const N: usize>(
+    name: &str,
+    banks: &[[u8; N]],
+    rebuilt: &[[u8; N]],
+    differences: &mut Vec<String>,
+) {
+    if banks.len() != rebuilt.len() {
+        differences.push(format!(
+            "{name} bank count is {} but rebuilt is {}",
+            banks.len(),
+            rebuilt.len()
+        ));
+    }
+    for (i, (bank, rebuilt)) in banks.iter().zip(rebuilt).enumerate() {
+        if bank != rebuilt {
+            differences.push(format!("{name} bank {i} differs"));
+        }
+    }
+}
+
+fn sections_by_id(game_file: &GameFile) -> Result<BTreeMap<u8, Vec<u8>>, GameFileError> {
+    let bytes = game_file.sections_as_bytes()?;
+    Ok(read_sections(&mut BufReader::new(&*bytes))?
+        .into_iter()
+        .collect())
+}
+
+fn build(
+    manifest: &Manifest,
+    base_dir: &Path,
+    cache: Option<&mut BuildCache>,
+    timestamp: Option<u64>,
+) -> Result<BuildOutput, GameFileError> {
+    let unresolved = manifest.unresolved();
+    if !unresolved.is_empty() {
+        return Err(ManifestParsingError(unresolved.join("\n")));
+    }
+    let mut manifest = manifest.clone();
+    let file_expansions = manifest.expand_files(base_dir)?;
+    let manifest = &manifest;
+    let (main_code, main_code_size) = read_code::<MAIN_CODE>(&base_dir.join(&manifest.main_code))?;
+
+    if manifest.code_files.len() > MAX_BANK_COUNT {
+        return Err(InvalidCode(format!(
+            "Too many code files, max is {} and was {}",
+            MAX_BANK_COUNT,
+            manifest.code_files.len()
+        )));
+    }
+    let mut code_banks = vec![];
+    let mut code_bank_sizes = vec![];
+    for file in &manifest.code_files {
+        let (bank, size) = read_code::<CODE_BANK>(&base_dir.join(file))?;
+        code_banks.push(bank);
+        code_bank_sizes.push(size);
+    }
+
+    let (packed, cache_report) = match cache {
+        Some(cache) => {
+            let (packed, atlas_banks) = cache.pack_atlases(base_dir, &manifest.atlas_files)?;
+            (packed, Some(CacheReport { atlas_banks }))
+        }
+        None => {
+            let mut atlases = vec![];
+            for file in &manifest.atlas_files {
+                atlases.push(AtlasFile::read(base_dir.join(file))?);
+            }
+            (pack_atlases(&atlases)?, None)
+        }
+    };
+    let atlas_placements = packed.placements.clone();
+
+    let header = manifest.header(code_banks.len() as u8, packed.banks.len() as u8);
+    let mut game_file = GameFile::new(header, main_code, code_banks, vec![]);
+    packed.apply(&mut game_file);
+
+    if !manifest.palette_files.is_empty() {
+        let paths: Vec<PathBuf> = manifest
+            .palette_files
+            .iter()
+            .map(|file| base_dir.join(file))
+            .collect();
+        game_file.palettes = Some(PaletteSet::from_files(&paths)?);
+    }
+
+    game_file.metadata = manifest
+        .metadata
+        .clone()
+        .filter(|metadata| !metadata.is_empty());
+    if !manifest.localizations.is_empty() {
+        game_file.localizations = Some(manifest.localizations.clone());
+    }
+    if let Some(file) = &manifest.icon_file {
+        game_file.icon = Some(GameImage::read(base_dir.join(file))?);
+    }
+    if let Some(file) = &manifest.cover_file {
+        game_file.cover = Some(GameImage::read(base_dir.join(file))?);
+    }
+    if manifest.build_info {
+        let timestamp = match timestamp {
+            Some(timestamp) => timestamp,
+            None => build_timestamp()?,
+        };
+        game_file.build_info = Some(BuildInfo::new(manifest, base_dir, timestamp)?);
+    }
+
+    game_file.validate().map_err(ValidationFailed)?;
+
+    Ok(BuildOutput {
+        game_file,
+        report: BuildReport {
+            main_code_size,
+            code_bank_sizes,
+            atlas_placements,
+            file_expansions,
+        },
+        cache: cache_report,
+    })
+}
+
+/// Read code file and pad to `N` bytes
+fn read_code<const N: usize>(path: &Path) -> Result<([u8; N], usize), GameFileError> {
+    validate_file(path, false)?;
+    let mut bytes = fs::read(path).map_err(|e| FileAccessError(e, "reading code file"))?;
+    let size = bytes.len();
+    if size > N {
+        return Err(InvalidCode(format!(
+            "{} is too large, max is {} and was {}",
+            path.display(),
+            N,
+            size
+        )));
+    }
+    bytes.resize(N, 0);
+    Ok((convert_vec(bytes), size))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::atlas_file::ATLAS_SPRITE_SIZE;
+    use crate::build_cache::RebuildReason;
+    use crate::localization::Localizations;
+    use crate::read_write_impl::Readable;
+    use crate::test_utils::TestDir;
+
+    fn test_manifest() -> Manifest {
+        Manifest {
+            id: String::from("com.example.game"),
+            name: String::from("Game"),
+            author: String::from("Author"),
+            version: String::from("1.0.0"),
+            build: 3.into(),
+            main_code: String::from("main.bin"),
+            min_maikor_version: 1,
+            compiled_for_maikor_version: None,
+            code_files: vec![String::from("code.bin")],
+            atlas_files: vec![String::from("a.bin"), String::from("b.bin")],
+            ram_banks: 2,
+            palette_files: vec![],
+            metadata: None,
+            icon_file: None,
+            cover_file: None,
+            localizations: Localizations::new(),
+            build_info: false,
+            profiles: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn build() {
+        let dir = TestDir::new("build");
+        fs::write(dir.join("main.bin"), [1, 2, 3]).unwrap();
+        fs::write(dir.join("code.bin"), [4; 10]).unwrap();
+        fs::write(dir.join("a.bin"), [5; ATLAS_SPRITE_SIZE * 2]).unwrap();
+        fs::write(dir.join("b.bin"), [6; ATLAS_SPRITE_SIZE]).unwrap();
+
+        let output = build_from_manifest(&test_manifest(), &dir).unwrap();
+        let file = output.game_file;
+        assert_eq!(file.header.build, 3);
+        assert_eq!(file.header.code_bank_count, 1);
+        assert_eq!(file.header.atlas_bank_count, 1);
+        assert_eq!(file.header.ram_bank_count, 2);
+        assert_eq!(file.main_code[..4], [1, 2, 3, 0]);
+        assert_eq!(file.code_banks[0][9..11], [4, 0]);
+        assert_eq!(file.atlases[0][ATLAS_SPRITE_SIZE * 2], 6);
+        assert_eq!(output.report.main_code_size, 3);
+        assert_eq!(output.report.code_bank_sizes, vec![10]);
+        assert_eq!(output.report.atlas_placements[1].tile_offset, 2);
+
+        fs::write(dir.join("code.bin"), vec![4; CODE_BANK + 1]).unwrap();
+        assert!(build_from_manifest(&test_manifest(), &dir).is_err());
+    }
+
+    #[test]
+    fn build_info_and_verify() {
+        let dir = TestDir::new("verify");
+        fs::write(dir.join("main.bin"), [1, 2, 3]).unwrap();
+        fs::write(dir.join("code.bin"), [4; 10]).unwrap();
+        fs::write(dir.join("a.bin"), [5; ATLAS_SPRITE_SIZE * 2]).unwrap();
+        fs::write(dir.join("b.bin"), [6; ATLAS_SPRITE_SIZE]).unwrap();
+        let manifest = Manifest {
+            build_info: true,
+            ..test_manifest()
+        };
+        let file = build_from_manifest(&manifest, &dir).unwrap().game_file;
+        let info = file.build_info.as_ref().unwrap();
+        assert_eq!(info.inputs.len(), 4);
+        assert_eq!(info.inputs[3].file, "b.bin");
+        assert_eq!(info.tool, crate::build_info::TOOL_NAME);
+        let bytes = file.as_bytes().unwrap();
+        let file = GameFile::from_reader(&mut BufReader::new(&*bytes)).unwrap();
+
+        let verification = verify_build(&manifest, &dir, &file).unwrap();
+        assert!(verification.matches);
+        assert!(verification.differences.is_empty());
+
+        fs::write(dir.join("b.bin"), [7; ATLAS_SPRITE_SIZE]).unwrap();
+        let verification = verify_build(&manifest, &dir, &file).unwrap();
+        assert!(!verification.matches);
+        assert_eq!(
+            verification.differences,
+            vec![
+                "b.bin has changed",
+                "Atlas bank 0 differs",
+                "BuildInfo section differs"
+            ]
+        );
+
+        let verification = verify_build(&test_manifest(), &dir, &file).unwrap();
+        assert!(verification
+            .differences
+            .contains(&String::from("BuildInfo section isn't in rebuilt file")));
+    }
+
+    #[test]
+    fn cached_build() {
+        let dir = TestDir::new("cached-build");
+        let cache_dir = dir.join("cache");
+        fs::write(dir.join("main.bin"), [1, 2, 3]).unwrap();
+        fs::write(dir.join("code.bin"), [4; 10]).unwrap();
+        fs::write(dir.join("a.bin"), vec![5; ATLAS_SPRITE_SIZE * 100]).unwrap();
+        fs::write(dir.join("b.bin"), vec![6; ATLAS_SPRITE_SIZE * 100]).unwrap();
+        let manifest = test_manifest();
+        let expected = build_from_manifest(&manifest, &dir)
+            .unwrap()
+            .game_file
+            .as_bytes()
+            .unwrap();
+        let build = |cache: &mut BuildCache| {
+            let output = build_from_manifest_cached(&manifest, &dir, cache).unwrap();
+            cache.save().unwrap();
+            assert_eq!(output.game_file.as_bytes().unwrap(), expected);
+            output.cache.unwrap()
+        };
+
+        let mut cache = BuildCache::open(&cache_dir).unwrap();
+        let report = build(&mut cache);
+        assert_eq!(report.rebuilt_count(), 2);
+        assert_eq!(report.atlas_banks[0].reasons(), &[RebuildReason::NotCached]);
+
+        let mut cache = BuildCache::open(&cache_dir).unwrap();
+        let report = build(&mut cache);
+        assert_eq!(report.rebuilt_count(), 0);
+
+        fs::write(dir.join("b.bin"), vec![7; ATLAS_SPRITE_SIZE * 100]).unwrap();
+        let expected = build_from_manifest(&manifest, &dir)
+            .unwrap()
+            .game_file
+            .as_bytes()
+            .unwrap();
+        let output = build_from_manifest_cached(&manifest, &dir, &mut cache).unwrap();
+        assert_eq!(output.game_file.as_bytes().unwrap(), expected);
+        let report = output.cache.unwrap();
+        assert!(report.atlas_banks[0].is_reused());
+        assert_eq!(
+            report.atlas_banks[1].reasons(),
+            &[RebuildReason::SourceChanged(String::from("b.bin"))]
+        );
+        cache.save().unwrap();
+
+        for entry in fs::read_dir(cache_dir.join("banks")).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "bin") {
+                fs::remove_file(path).unwrap();
+            }
+        }
+        let output = build_from_manifest_cached(&manifest, &dir, &mut cache).unwrap();
+        assert_eq!(output.game_file.as_bytes().unwrap(), expected);
+        assert_eq!(
+            output.cache.unwrap().atlas_banks[0].reasons(),
+            &[RebuildReason::DataMissing]
+        );
+    }
+
+    #[test]
+    fn cache_in_project_dir() {
+        let dir = TestDir::new("cache-in-project");
+        fs::write(dir.join("main.bin"), [1, 2, 3]).unwrap();
+        fs::write(dir.join("code.bin"), [4; 10]).unwrap();
+        fs::write(dir.join("a.bin"), [5; ATLAS_SPRITE_SIZE]).unwrap();
+        fs::write(dir.join("b.bin"), [6; ATLAS_SPRITE_SIZE]).unwrap();
+        fs::create_dir_all(dir.join("banks")).unwrap();
+        fs::write(dir.join("banks/notes.bin"), [1]).unwrap();
+
+        let mut cache = BuildCache::open(&dir).unwrap();
+        build_from_manifest_cached(&test_manifest(), &dir, &mut cache).unwrap();
+        cache.save().unwrap();
+        fs::write(dir.join("b.bin"), [7; ATLAS_SPRITE_SIZE]).unwrap();
+        build_from_manifest_cached(&test_manifest(), &dir, &mut cache).unwrap();
+        cache.save().unwrap();
+
+        for file in ["main.bin", "code.bin", "a.bin", "b.bin", "banks/notes.bin"] {
+            assert!(dir.join(file).is_file(), "{file} was deleted");
+        }
+        let banks = fs::read_dir(dir.join("banks")).unwrap().count();
+        assert_eq!(banks, 2);
+    }
+}
@@ -0,0 +1,327 @@
+use crate::atlas_file::{AtlasFile, ATLAS_SPRITE_SIZE};
+use crate::atlas_packer::{plan_atlases, PackedAtlases};
+use crate::checksum::sha256_hex;
+use crate::constants::mem::ATLAS_BANK;
+use crate::file_utils::convert_vec;
+use crate::read_write_impl::{validate_file, Readable};
+use crate::GameFileError;
+use crate::GameFileError::{FileAccessError, InvalidAtlas};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+const INDEX_FILE: &str = "index.json";
+///Banks are stored in their own directory so the cache can share a directory with other files
+const BANKS_DIR: &str = "banks";
+const BANK_EXT: &str = "bin";
+///Cache is discarded if it was written by a different version of this crate
+const CACHE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Why a bank couldn't be reused from the cache
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub enum RebuildReason {
+    ///Bank wasn't built before
+    NotCached,
+    ///File wasn't in this bank in the previous build
+    SourceAdded(String),
+    ///File was in this bank in the previous build but isn't now
+    SourceRemoved(String),
+    ///File content has changed since the previous build
+    SourceChanged(String),
+    ///Same files but at different positions in the bank
+    LayoutChanged,
+    ///Cached bank content is missing or corrupt
+    DataMissing,
+}
+
+impl Display for RebuildReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RebuildReason::NotCached => write!(f, "not cached"),
+            RebuildReason::SourceAdded(file) => write!(f, "{file} added"),
+            RebuildReason::SourceRemoved(file) => write!(f, "{file} removed"),
+            RebuildReason::SourceChanged(file) => write!(f, "{file} changed"),
+            RebuildReason::LayoutChanged => write!(f, "layout changed"),
+            RebuildReason::DataMissing => write!(f, "cached data missing"),
+        }
+    }
+}
+
+/// Whether a bank was reused from the cache
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub enum BankStatus {
+    Reused,
+    Rebuilt(Vec<RebuildReason>),
+}
+
+impl BankStatus {
+    pub fn is_reused(&self) -> bool {
+        matches!(self, BankStatus::Reused)
+    }
+
+    /// Why the bank was rebuilt, empty if it was reused
+    pub fn reasons(&self) -> &[RebuildReason] {
+        match self {
+            BankStatus::Reused => &[],
+            BankStatus::Rebuilt(reasons) => reasons,
+        }
+    }
+}
+
+/// Status of each atlas bank in a cached build
+///
+/// Code banks aren't cached as they're copied from their files without any processing
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct CacheReport {
+    pub atlas_banks: Vec<BankStatus>,
+}
+
+impl CacheReport {
+    pub fn rebuilt_count(&self) -> usize {
+        self.atlas_banks
+            .iter()
+            .filter(|status| !status.is_reused())
+            .count()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+struct CachedSource {
+    file: String,
+    hash: String,
+    tile_offset: u8,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+struct CachedBank {
+    sources: Vec<CachedSource>,
+    ///Hash of the bank content, atlas banks are stored as `banks/HASH.bin`
+    hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheIndex {
+    version: String,
+    atlas_banks: Vec<CachedBank>,
+}
+
+impl Default for CacheIndex {
+    fn default() -> Self {
+        CacheIndex {
+            version: String::from(CACHE_VERSION),
+            atlas_banks: vec![],
+        }
+    }
+}
+
+/// Results of previous builds, used by [build_from_manifest_cached](crate::build::build_from_manifest_cached)
+/// to skip re-processing atlas banks whose source files haven't changed
+///
+/// Files are identified by content hash (SHA-256) so timestamps don't matter. Atlas banks are
+/// reused without parsing or packing their atlases, code banks are always read from their files
+#[derive(Debug)]
+pub struct BuildCache {
+    dir: PathBuf,
+    index: CacheIndex,
+}
+
+impl BuildCache {
+    /// Load the cache from `dir`, the cache is empty if `dir` doesn't exist or was written by a
+    /// different version
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<BuildCache, GameFileError> {
+        let dir = dir.as_ref().to_path_buf();
+        let path = dir.join(INDEX_FILE);
+        let index = if path.is_file() {
+            let text =
+                fs::read_to_string(&path).map_err(|e| FileAccessError(e, "reading build cache"))?;
+            serde_json::from_str::<CacheIndex>(&text)
+                .ok()
+                .filter(|index| index.version == CACHE_VERSION)
+                .unwrap_or_default()
+        } else {
+            CacheIndex::default()
+        };
+        Ok(BuildCache { dir, index })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Forget all previous builds, the next build rebuilds every bank
+    pub fn clear(&mut self) {
+        self.index = CacheIndex::default();
+    }
+
+    /// Write the index and delete stored banks that are no longer used
+    ///
+    /// Only files named like a stored bank (`banks/HASH.bin`) are deleted
+    pub fn save(&self) -> Result<(), GameFileError> {
+        fs::create_dir_all(&self.dir).map_err(|e| FileAccessError(e, "creating build cache"))?;
+        let json = serde_json::to_string_pretty(&self.index).expect("cache index is valid JSON");
+        fs::write(self.dir.join(INDEX_FILE), json)
+            .map_err(|e| FileAccessError(e, "writing build cache"))?;
+
+        let used: BTreeSet<String> = self
+            .index
+            .atlas_banks
+            .iter()
+            .map(|bank| format!("{}.{BANK_EXT}", bank.hash))
+            .collect();
+        let banks_dir = self.dir.join(BANKS_DIR);
+        if !banks_dir.is_dir() {
+            return Ok(());
+        }
+        let entries =
+            fs::read_dir(&banks_dir).map_err(|e| FileAccessError(e, "reading build cache"))?;
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if is_bank_file(&name) && !used.contains(&name) {
+                fs::remove_file(entry.path())
+                    .map_err(|e| FileAccessError(e, "cleaning build cache"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pack atlas files (relative to `base_dir`) the same way as [pack_atlases](crate::atlas_packer::pack_atlases),
+    /// reusing banks where none of the atlases in it have changed
+    pub(crate) fn pack_atlases(
+        &mut self,
+        base_dir: &Path,
+        files: &[String],
+    ) -> Result<(PackedAtlases, Vec<BankStatus>), GameFileError> {
+        let mut contents = vec![];
+        let mut tile_counts = vec![];
+        for file in files {
+            let path = base_dir.join(file);
+            validate_file(&path, false)?;
+            let bytes = fs::read(&path).map_err(|e| FileAccessError(e, "reading atlas file"))?;
+            if !bytes.len().is_multiple_of(ATLAS_SPRITE_SIZE) {
+                return Err(InvalidAtlas(format!(
+                    "Content must be multiple of {}",
+                    ATLAS_SPRITE_SIZE
+                )));
+            }
+            tile_counts.push(bytes.len() / ATLAS_SPRITE_SIZE);
+            contents.push(bytes);
+        }
+        let (placements, bank_count) = plan_atlases(&tile_counts)?;
+
+        let mut banks = vec![];
+        let mut statuses = vec![];
+        let mut cached_banks = vec![];
+        for bank_idx in 0..bank_count {
+            let members: Vec<usize> = (0..files.len())
+                .filter(|&i| placements[i].bank as usize == bank_idx)
+                .collect();
+            let sources: Vec<CachedSource> = members
+                .iter()
+                .map(|&i| CachedSource {
+                    file: files[i].clone(),
+                    hash: sha256_hex(&contents[i]),
+                    tile_offset: placements[i].tile_offset,
+                })
+                .collect();
+            let cached = self.index.atlas_banks.get(bank_idx);
+            let mut reasons = compare(cached, &sources);
+            let mut bank = None;
+            if reasons.is_empty() {
+                bank = cached.and_then(|cached| self.read_bank(&cached.hash));
+                if bank.is_none() {
+                    reasons.push(RebuildReason::DataMissing);
+                }
+            }
+            let bank = match bank {
+                Some(bank) => bank,
+                None => {
+                    let mut bank = [0; ATLAS_BANK];
+                    for &i in &members {
+                        let atlas = AtlasFile::from_reader(&mut BufReader::new(&*contents[i]))?;
+                        for (j, image) in atlas.images().iter().enumerate() {
+                            let start =
+                                (placements[i].tile_offset as usize + j) * ATLAS_SPRITE_SIZE;
+                            bank[start..start + ATLAS_SPRITE_SIZE].copy_from_slice(image);
+                        }
+                    }
+                    bank
+                }
+            };
+            let hash = sha256_hex(&bank);
+            self.write_bank(&hash, &bank)?;
+            statuses.push(status(reasons));
+            cached_banks.push(CachedBank { sources, hash });
+            banks.push(bank);
+        }
+        self.index.atlas_banks = cached_banks;
+        Ok((PackedAtlases { banks, placements }, statuses))
+    }
+
+    fn bank_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(BANKS_DIR).join(format!("{hash}.{BANK_EXT}"))
+    }
+
+    /// Stored bank content, if it exists and matches `hash`
+    fn read_bank(&self, hash: &str) -> Option<[u8; ATLAS_BANK]> {
+        let bytes = fs::read(self.bank_path(hash)).ok()?;
+        if bytes.len() == ATLAS_BANK && sha256_hex(&bytes) == hash {
+            Some(convert_vec(bytes))
+        } else {
+            None
+        }
+    }
+
+    fn write_bank(&self, hash: &str, bank: &[u8]) -> Result<(), GameFileError> {
+        let path = self.bank_path(hash);
+        if !path.is_file() {
+            fs::create_dir_all(self.dir.join(BANKS_DIR))
+                .map_err(|e| FileAccessError(e, "creating build cache"))?;
+            fs::write(path, bank).map_err(|e| FileAccessError(e, "writing build cache"))?;
+        }
+        Ok(())
+    }
+}
+
+/// If `name` is a stored bank, `HASH.bin` where `HASH` is a SHA-256 hex string
+fn is_bank_file(name: &str) -> bool {
+    name.strip_suffix(&format!(".{BANK_EXT}"))
+        .is_some_and(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn status(reasons: Vec<RebuildReason>) -> BankStatus {
+    if reasons.is_empty() {
+        BankStatus::Reused
+    } else {
+        BankStatus::Rebuilt(reasons)
+    }
+}
+
+/// Differences between the previous and current sources of a bank
+fn compare(cached: Option<&CachedBank>, sources: &[CachedSource]) -> Vec<RebuildReason> {
+    let cached = match cached {
+        Some(cached) => cached,
+        None => return vec![RebuildReason::NotCached],
+    };
+    let mut reasons = vec![];
+    for source in sources {
+        match cached.sources.iter().find(|old| old.file == source.file) {
+            None => reasons.push(RebuildReason::SourceAdded(source.file.clone())),
+            Some(old) if old.hash != source.hash => {
+                reasons.push(RebuildReason::SourceChanged(source.file.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    for old in &cached.sources {
+        if !sources.iter().any(|source| source.file == old.file) {
+            reasons.push(RebuildReason::SourceRemoved(old.file.clone()));
+        }
+    }
+    if reasons.is_empty() && cached.sources != sources {
+        reasons.push(RebuildReason::LayoutChanged);
+    }
+    reasons
+}
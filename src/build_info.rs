@@ -0,0 +1,231 @@
+use crate::checksum::sha256_hex;
+use crate::file_utils::{read_header_string, ReaderExt};
+use crate::game_header::convert_string;
+use crate::manifest::Manifest;
+use crate::read_write_impl::{Readable, Writeable};
+use crate::GameFileError;
+use crate::GameFileError::{FileAccessError, InvalidBuildInfo};
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable used for the build timestamp, see <https://reproducible-builds.org/specs/source-date-epoch/>
+pub const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+/// Name and version written as [BuildInfo::tool]
+pub const TOOL_NAME: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+const HASH_LEN: usize = 64;
+
+/// Hash of a file used to build a game file
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct InputHash {
+    ///Path relative to the manifest
+    pub file: String,
+    ///SHA-256 of the content, as hex
+    pub hash: String,
+}
+
+/// How a game file was built (optional section), used to check it can be reproduced
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct BuildInfo {
+    ///SHA-256 of the resolved manifest (as compact JSON), as hex
+    pub manifest_hash: String,
+    ///Every file read while building, in manifest order
+    pub inputs: Vec<InputHash>,
+    ///Name and version of the tool that built the file
+    pub tool: String,
+    ///Seconds since the Unix epoch, from `SOURCE_DATE_EPOCH` if set
+    pub timestamp: u64,
+}
+
+impl BuildInfo {
+    /// Hash `manifest` and the files it references (relative to `base_dir`)
+    ///
+    /// `manifest` must already be resolved and expanded
+    pub(crate) fn new(
+        manifest: &Manifest,
+        base_dir: &Path,
+        timestamp: u64,
+    ) -> Result<BuildInfo, GameFileError> {
+        let json = serde_json::to_string(manifest).expect("manifest is valid JSON");
+        let mut inputs = vec![];
+        let files = [&manifest.main_code]
+            .into_iter()
+            .chain(&manifest.code_files)
+            .chain(&manifest.atlas_files)
+            .chain(&manifest.palette_files)
+            .chain(&manifest.icon_file)
+            .chain(&manifest.cover_file);
+        for file in files {
+            let bytes = fs::read(base_dir.join(file))
+                .map_err(|e| FileAccessError(e, "reading file for build info"))?;
+            inputs.push(InputHash {
+                file: file.clone(),
+                hash: sha256_hex(&bytes),
+            });
+        }
+        Ok(BuildInfo {
+            manifest_hash: sha256_hex(json.as_bytes()),
+            inputs,
+            tool: String::from(TOOL_NAME),
+            timestamp,
+        })
+    }
+
+    /// Changes from `self` (an earlier build) to `other`, i.e. changed input files
+    pub fn differences(&self, other: &BuildInfo) -> Vec<String> {
+        let mut differences = vec![];
+        if self.manifest_hash != other.manifest_hash {
+            differences.push(String::from("Manifest has changed"));
+        }
+        if self.tool != other.tool {
+            differences.push(format!("Built with {}, now {}", self.tool, other.tool));
+        }
+        for input in &self.inputs {
+            match other.inputs.iter().find(|other| other.file == input.file) {
+                None => differences.push(format!("{} is no longer used", input.file)),
+                Some(other) if other.hash != input.hash => {
+                    differences.push(format!("{} has changed", input.file))
+                }
+                Some(_) => {}
+            }
+        }
+        for input in &other.inputs {
+            if !self.inputs.iter().any(|own| own.file == input.file) {
+                differences.push(format!("{} is new", input.file));
+            }
+        }
+        differences
+    }
+}
+
+/// Build timestamp, `SOURCE_DATE_EPOCH` if set otherwise the current time
+pub fn build_timestamp() -> Result<u64, GameFileError> {
+    match env::var(SOURCE_DATE_EPOCH) {
+        Ok(value) => value.trim().parse().map_err(|_| {
+            InvalidBuildInfo(format!(
+                "{SOURCE_DATE_EPOCH} must be a number, was '{value}'"
+            ))
+        }),
+        Err(_) => Ok(SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default()),
+    }
+}
+
+impl Writeable for BuildInfo {
+    fn as_bytes(&self) -> Result<Vec<u8>, GameFileError> {
+        if self.inputs.len() > u16::MAX as usize {
+            return Err(InvalidBuildInfo(format!(
+                "Too many inputs, max is {}",
+                u16::MAX
+            )));
+        }
+        let mut output = convert_string("Manifest hash", &self.manifest_hash)?;
+        output.extend_from_slice(&convert_string("Tool", &self.tool)?);
+        output.extend_from_slice(&self.timestamp.to_be_bytes());
+        output.extend_from_slice(&(self.inputs.len() as u16).to_be_bytes());
+        for input in &self.inputs {
+            output.extend_from_slice(&convert_string("Input file", &input.file)?);
+            output.extend_from_slice(&convert_string("Input hash", &input.hash)?);
+        }
+        Ok(output)
+    }
+}
+
+impl Readable for BuildInfo {
+    fn from_reader<R: ReaderExt>(reader: &mut R) -> Result<Self, GameFileError>
+    where
+        Self: Sized,
+    {
+        let manifest_hash = read_header_string(reader, "Manifest hash", "reading manifest hash")?;
+        let tool = read_header_string(reader, "Tool", "reading tool")?;
+        let timestamp = reader
+            .read_u64()
+            .map_err(|e| FileAccessError(e, "reading build timestamp"))?;
+        let count = reader
+            .read_u16()
+            .map_err(|e| FileAccessError(e, "reading input count"))?;
+        let mut inputs = vec![];
+        for _ in 0..count {
+            let file = read_header_string(reader, "Input file", "reading input file")?;
+            let hash = read_header_string(reader, "Input hash", "reading input hash")?;
+            inputs.push(InputHash { file, hash });
+        }
+        Ok(BuildInfo {
+            manifest_hash,
+            inputs,
+            tool,
+            timestamp,
+        })
+    }
+}
+
+impl BuildInfo {
+    /// Check all hashes are SHA-256 hex strings
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let is_hash =
+            |hash: &str| hash.len() == HASH_LEN && hash.chars().all(|c| c.is_ascii_hexdigit());
+        let mut errors = vec![];
+        if !is_hash(&self.manifest_hash) {
+            errors.push(String::from(
+                "Build info manifest hash is not a SHA-256 hash",
+            ));
+        }
+        for input in &self.inputs {
+            if !is_hash(&input.hash) {
+                errors.push(format!(
+                    "Build info hash for {} is not a SHA-256 hash",
+                    input.file
+                ));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn read_write() {
+        let info = BuildInfo {
+            manifest_hash: sha256_hex(b"manifest"),
+            inputs: vec![
+                InputHash {
+                    file: String::from("main.bin"),
+                    hash: sha256_hex(b"main"),
+                },
+                InputHash {
+                    file: String::from("atlases/a.bin"),
+                    hash: sha256_hex(b"atlas"),
+                },
+            ],
+            tool: String::from(TOOL_NAME),
+            timestamp: 1_700_000_000,
+        };
+        assert_eq!(info.validate(), Ok(()));
+        let bytes = info.as_bytes().unwrap();
+        let parsed = BuildInfo::from_reader(&mut BufReader::new(&*bytes)).unwrap();
+        assert_eq!(parsed, info);
+
+        let mut changed = info.clone();
+        changed.inputs[1].hash = sha256_hex(b"other");
+        changed.inputs.remove(0);
+        assert_eq!(
+            info.differences(&changed),
+            vec!["main.bin is no longer used", "atlases/a.bin has changed"]
+        );
+
+        changed.manifest_hash = String::from("abc");
+        assert_eq!(changed.validate().unwrap_err().len(), 1);
+    }
+}
@@ -0,0 +1,297 @@
+use crate::palette::{Color, Palette};
+use crate::GameFileError;
+use crate::GameFileError::InvalidColor;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Color depths that colors can be quantized to
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ColorDepth {
+    ///8 bits per channel
+    Rgb888,
+    ///5 bits red, 6 bits green, 5 bits blue
+    Rgb565,
+    ///5 bits per channel
+    Rgb555,
+}
+
+/// Color depth used by Maikor hardware (palettes are stored as 3 bytes per color)
+pub const NATIVE_COLOR_DEPTH: ColorDepth = ColorDepth::Rgb888;
+
+impl Color {
+    /// Parse `#RRGGBB`, `RRGGBB`, `#RGB` or `RGB`
+    pub fn from_hex(text: &str) -> Result<Color, GameFileError> {
+        let hex = text.trim();
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(InvalidColor(format!("'{text}' is not a hex color")));
+        }
+        let value = u32::from_str_radix(hex, 16)
+            .map_err(|_| InvalidColor(format!("'{text}' is not a hex color")))?;
+        match hex.len() {
+            6 => Ok(Color::from_u32(value)),
+            3 => {
+                let expand = |v: u32| (v as u8 & 0xF) * 0x11;
+                Ok(Color::new(
+                    expand(value >> 8),
+                    expand(value >> 4),
+                    expand(value),
+                ))
+            }
+            _ => Err(InvalidColor(format!(
+                "'{text}' must be in the format #RRGGBB or #RGB"
+            ))),
+        }
+    }
+
+    /// Returns color as `#RRGGBB`
+    pub fn to_hex(&self) -> String {
+        format!("#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+    }
+
+    /// Create from `0x00RRGGBB`, the top byte is ignored
+    pub fn from_u32(value: u32) -> Color {
+        let [_, r, g, b] = value.to_be_bytes();
+        Color::new(r, g, b)
+    }
+
+    /// Returns color as `0x00RRGGBB`
+    pub fn to_u32(&self) -> u32 {
+        u32::from_be_bytes([0, self.r, self.g, self.b])
+    }
+}
+
+impl Color {
+    /// Returns (hue (0..360), saturation (0..=1), lightness (0..=1))
+    pub fn to_hsl(&self) -> (f32, f32, f32) {
+        let (hue, max, min) = self.hue_max_min();
+        let lightness = (max + min) / 2.0;
+        let delta = max - min;
+        let saturation = if delta == 0.0 {
+            0.0
+        } else {
+            delta / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+        (hue, saturation, lightness)
+    }
+
+    /// `hue` is in degrees, `saturation` and `lightness` are 0..=1
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Color {
+        let saturation = saturation.clamp(0.0, 1.0);
+        let lightness = lightness.clamp(0.0, 1.0);
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        Color::from_hue_chroma(hue, chroma, lightness - chroma / 2.0)
+    }
+
+    /// Returns (hue (0..360), saturation (0..=1), value (0..=1))
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let (hue, max, min) = self.hue_max_min();
+        let saturation = if max == 0.0 { 0.0 } else { (max - min) / max };
+        (hue, saturation, max)
+    }
+
+    /// `hue` is in degrees, `saturation` and `value` are 0..=1
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Color {
+        let saturation = saturation.clamp(0.0, 1.0);
+        let value = value.clamp(0.0, 1.0);
+        let chroma = value * saturation;
+        Color::from_hue_chroma(hue, chroma, value - chroma)
+    }
+
+    fn hue_max_min(&self) -> (f32, f32, f32) {
+        let r = self.r as f32 / 255.0;
+        let g = self.g as f32 / 255.0;
+        let b = self.b as f32 / 255.0;
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        (hue, max, min)
+    }
+
+    fn from_hue_chroma(hue: f32, chroma: f32, offset: f32) -> Color {
+        let hue = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+        let (r, g, b) = match hue as u8 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let convert = |v: f32| ((v + offset) * 255.0).round().clamp(0.0, 255.0) as u8;
+        Color::new(convert(r), convert(g), convert(b))
+    }
+}
+
+impl Color {
+    pub fn to_rgb565(&self) -> u16 {
+        ((self.r as u16 >> 3) << 11) | ((self.g as u16 >> 2) << 5) | (self.b as u16 >> 3)
+    }
+
+    pub fn from_rgb565(value: u16) -> Color {
+        Color::new(
+            expand_bits((value >> 11) as u8, 5),
+            expand_bits((value >> 5) as u8, 6),
+            expand_bits(value as u8, 5),
+        )
+    }
+
+    pub fn to_rgb555(&self) -> u16 {
+        ((self.r as u16 >> 3) << 10) | ((self.g as u16 >> 3) << 5) | (self.b as u16 >> 3)
+    }
+
+    pub fn from_rgb555(value: u16) -> Color {
+        Color::new(
+            expand_bits((value >> 10) as u8, 5),
+            expand_bits((value >> 5) as u8, 5),
+            expand_bits(value as u8, 5),
+        )
+    }
+
+    /// Returns the closest color that can be represented in `depth`
+    pub fn quantize(&self, depth: ColorDepth) -> Color {
+        match depth {
+            ColorDepth::Rgb888 => *self,
+            ColorDepth::Rgb565 => Color::from_rgb565(self.to_rgb565()),
+            ColorDepth::Rgb555 => Color::from_rgb555(self.to_rgb555()),
+        }
+    }
+}
+
+/// Scale a `bits` wide value up to 8 bits, so that the max value maps to 255
+fn expand_bits(value: u8, bits: u8) -> u8 {
+    let value = value & ((1 << bits) - 1);
+    (value << (8 - bits)) | (value >> (2 * bits - 8))
+}
+
+impl Color {
+    /// Perceptual distance between colors, using the 'redmean' weighted euclidean distance
+    ///
+    /// 0 means the colors are identical, the max (black to white) is ~765
+    pub fn distance(&self, other: &Color) -> f32 {
+        let mean_r = (self.r as f32 + other.r as f32) / 2.0;
+        let r = self.r as f32 - other.r as f32;
+        let g = self.g as f32 - other.g as f32;
+        let b = self.b as f32 - other.b as f32;
+        ((2.0 + mean_r / 256.0) * r * r + 4.0 * g * g + (2.0 + (255.0 - mean_r) / 256.0) * b * b)
+            .sqrt()
+    }
+}
+
+impl Palette {
+    /// Index of the color in this palette closest to `color`
+    pub fn nearest(&self, color: &Color) -> usize {
+        let mut best = 0;
+        let mut best_distance = f32::MAX;
+        for (i, candidate) in self.colors.iter().enumerate() {
+            let distance = candidate.distance(color);
+            if distance < best_distance {
+                best = i;
+                best_distance = distance;
+            }
+        }
+        best
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Color::from_hex(&text).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hex() {
+        let color = Color::new(255, 16, 1);
+        assert_eq!(color.to_hex(), "#FF1001");
+        assert_eq!(Color::from_hex("#FF1001").unwrap(), color);
+        assert_eq!(Color::from_hex("ff1001").unwrap(), color);
+        assert_eq!(Color::from_hex("#F0A").unwrap(), Color::new(255, 0, 170));
+        assert!(Color::from_hex("#FF10").is_err());
+        assert!(Color::from_hex("#GG1001").is_err());
+        assert!(Color::from_hex("+FFFFF").is_err());
+    }
+
+    #[test]
+    fn packed() {
+        let color = Color::new(0x12, 0x34, 0x56);
+        assert_eq!(color.to_u32(), 0x123456);
+        assert_eq!(Color::from_u32(0xFF123456), color);
+    }
+
+    #[test]
+    fn hsl_hsv() {
+        assert_eq!(Color::new(255, 0, 0).to_hsl(), (0.0, 1.0, 0.5));
+        assert_eq!(Color::new(0, 0, 255).to_hsv(), (240.0, 1.0, 1.0));
+        assert_eq!(Color::from_hsl(120.0, 1.0, 0.5), Color::new(0, 255, 0));
+        assert_eq!(Color::from_hsv(300.0, 1.0, 1.0), Color::new(255, 0, 255));
+        for color in [
+            Color::new(12, 200, 99),
+            Color::new(1, 2, 3),
+            Color::new(250, 128, 0),
+            Color::new(77, 77, 77),
+        ] {
+            let (h, s, l) = color.to_hsl();
+            assert_eq!(Color::from_hsl(h, s, l), color);
+            let (h, s, v) = color.to_hsv();
+            assert_eq!(Color::from_hsv(h, s, v), color);
+        }
+    }
+
+    #[test]
+    fn quantize() {
+        let white = Color::new(255, 255, 255);
+        assert_eq!(white.to_rgb565(), 0xFFFF);
+        assert_eq!(white.to_rgb555(), 0x7FFF);
+        assert_eq!(white.quantize(ColorDepth::Rgb565), white);
+        assert_eq!(white.quantize(ColorDepth::Rgb555), white);
+        let color = Color::new(13, 130, 7);
+        assert_eq!(color.quantize(ColorDepth::Rgb888), color);
+        assert_eq!(color.quantize(ColorDepth::Rgb565), Color::new(8, 130, 0));
+        assert_eq!(color.quantize(ColorDepth::Rgb555), Color::new(8, 132, 0));
+    }
+
+    #[test]
+    fn nearest() {
+        let mut colors = [Color::new(0, 0, 0); 16];
+        colors[1] = Color::new(255, 0, 0);
+        colors[2] = Color::new(0, 255, 0);
+        colors[3] = Color::new(255, 255, 255);
+        let palette = Palette::new(None, colors);
+        assert_eq!(palette.nearest(&Color::new(200, 30, 30)), 1);
+        assert_eq!(palette.nearest(&Color::new(240, 240, 250)), 3);
+        assert_eq!(palette.nearest(&Color::new(10, 10, 10)), 0);
+        assert_eq!(Color::new(1, 2, 3).distance(&Color::new(1, 2, 3)), 0.0);
+    }
+
+    #[test]
+    fn serde() {
+        let palette = Palette::new(None, [Color::new(1, 2, 3); 16]);
+        let json = serde_json::to_string(&palette).unwrap();
+        assert!(json.starts_with("{\"colors\":[\"#010203\""));
+        let parsed: Palette = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, palette);
+        assert!(serde_json::from_str::<Color>("\"#XYZ\"").is_err());
+    }
+}
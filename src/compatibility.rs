@@ -0,0 +1,162 @@
+use crate::GameFileHeader;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+
+/// Maikor VM version as stored in game file headers
+///
+/// The high byte is the major version and the low byte is the minor version
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
+pub struct MaikorVersion(pub u16);
+
+/// Version (major.minor) of the `maikor-platform` dependency in `Cargo.toml`
+///
+/// `maikor-platform` doesn't export its version, so this must be updated with the dependency
+pub const PLATFORM_VERSION: MaikorVersion = MaikorVersion::new(0, 1);
+
+impl MaikorVersion {
+    pub const fn new(major: u8, minor: u8) -> Self {
+        Self(u16::from_be_bytes([major, minor]))
+    }
+
+    pub const fn major(&self) -> u8 {
+        self.0.to_be_bytes()[0]
+    }
+
+    pub const fn minor(&self) -> u8 {
+        self.0.to_be_bytes()[1]
+    }
+}
+
+impl From<u16> for MaikorVersion {
+    fn from(value: u16) -> Self {
+        Self(value)
+    }
+}
+
+impl Display for MaikorVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major(), self.minor())
+    }
+}
+
+/// Whether a game can run on a VM version
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Compatibility {
+    ///VM version is between the min and compiled for versions
+    Compatible,
+    ///VM is older than the min version required by the game
+    NeedsNewerVm { required: MaikorVersion },
+    ///VM is newer than the version the game was compiled for, it should work but hasn't been
+    ///tested by the developer
+    MayHaveIssues { compiled_for: MaikorVersion },
+}
+
+impl Compatibility {
+    /// True unless the VM is too old
+    pub fn can_run(&self) -> bool {
+        !matches!(self, Compatibility::NeedsNewerVm { .. })
+    }
+}
+
+impl Display for Compatibility {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compatibility::Compatible => write!(f, "Compatible"),
+            Compatibility::NeedsNewerVm { required } => {
+                write!(f, "Needs Maikor {required} or newer")
+            }
+            Compatibility::MayHaveIssues { compiled_for } => {
+                write!(f, "May have issues, made for Maikor {compiled_for}")
+            }
+        }
+    }
+}
+
+impl GameFileHeader {
+    pub fn min_version(&self) -> MaikorVersion {
+        MaikorVersion(self.min_maikor_version)
+    }
+
+    pub fn compiled_for_version(&self) -> MaikorVersion {
+        MaikorVersion(self.compiled_for_maikor_version)
+    }
+
+    /// Check if game can run on `vm_version`
+    pub fn compatibility(&self, vm_version: MaikorVersion) -> Compatibility {
+        if vm_version < self.min_version() {
+            Compatibility::NeedsNewerVm {
+                required: self.min_version(),
+            }
+        } else if vm_version > self.compiled_for_version() {
+            Compatibility::MayHaveIssues {
+                compiled_for: self.compiled_for_version(),
+            }
+        } else {
+            Compatibility::Compatible
+        }
+    }
+
+    /// Check if game can run on a VM using the same `maikor-platform` as this crate
+    pub fn platform_compatibility(&self) -> Compatibility {
+        self.compatibility(PLATFORM_VERSION)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(min: MaikorVersion, compiled_for: MaikorVersion) -> GameFileHeader {
+        GameFileHeader::new(
+            String::from("com.example.game"),
+            1,
+            compiled_for.0,
+            min.0,
+            0,
+            String::from("Game"),
+            String::from("1.0"),
+            String::from("Author"),
+            0,
+            1,
+        )
+    }
+
+    #[test]
+    fn version() {
+        let version = MaikorVersion::from(0x0203);
+        assert_eq!(version.major(), 2);
+        assert_eq!(version.minor(), 3);
+        assert_eq!(version, MaikorVersion::new(2, 3));
+        assert_eq!(version.to_string(), "2.3");
+        assert!(MaikorVersion::new(1, 255) < MaikorVersion::new(2, 0));
+    }
+
+    #[test]
+    fn compatibility() {
+        let header = header(MaikorVersion::new(1, 2), MaikorVersion::new(1, 4));
+        assert_eq!(
+            header.compatibility(MaikorVersion::new(1, 1)),
+            Compatibility::NeedsNewerVm {
+                required: MaikorVersion::new(1, 2)
+            }
+        );
+        assert_eq!(
+            header.compatibility(MaikorVersion::new(1, 2)),
+            Compatibility::Compatible
+        );
+        assert_eq!(
+            header.compatibility(MaikorVersion::new(1, 4)),
+            Compatibility::Compatible
+        );
+        let newer = header.compatibility(MaikorVersion::new(2, 0));
+        assert_eq!(
+            newer,
+            Compatibility::MayHaveIssues {
+                compiled_for: MaikorVersion::new(1, 4)
+            }
+        );
+        assert!(newer.can_run());
+        assert!(!header.compatibility(MaikorVersion::new(0, 9)).can_run());
+    }
+}
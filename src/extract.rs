@@ -0,0 +1,230 @@
+use crate::atlas_file::AtlasFile;
+use crate::icon::IMAGE_EXT;
+use crate::manifest::Manifest;
+use crate::manifest_format::ManifestFormat;
+use crate::palette::{Palette, PALETTE_EXT};
+use crate::read_write_impl::Writeable;
+use crate::GameFileError::{FileAccessError, InvalidPalette};
+use crate::{GameFile, GameFileError};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const MAIN_CODE_FILE: &str = "main.bin";
+pub const MANIFEST_FILE: &str = "manifest.json";
+const PALETTES_DIR: &str = "palettes";
+const ICON_FILE: &str = "icon";
+const COVER_FILE: &str = "cover";
+
+#[derive(Clone, Debug, Default)]
+pub struct ExtractOptions {
+    ///If set, each atlas bank is also written as a PNG using this palette
+    pub png_palette: Option<Palette>,
+    ///Format of the written manifest
+    pub manifest_format: ManifestFormat,
+}
+
+/// Write the content of `game_file` into `dir` as a project that can be built into the same
+/// game file
+///
+/// Creates `main.bin`, `code_N.bin`, `atlas_N.bin`, `palettes/NAME.mpal`, `icon.mimg`,
+/// `cover.mimg` and the manifest (`manifest.json` by default, see [ManifestFormat::file_name])
+///
+/// The build info section is dropped (`build_info` is `false`) as its manifest hash and timestamp
+/// wouldn't match a rebuild, `$` in the version is written as `$$` so it isn't read as a variable
+pub fn extract_project<P: AsRef<Path>>(
+    game_file: &GameFile,
+    dir: P,
+    options: &ExtractOptions,
+) -> Result<Manifest, GameFileError> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir).map_err(|e| FileAccessError(e, "creating project dir"))?;
+    let header = &game_file.header;
+
+    write(&dir.join(MAIN_CODE_FILE), &game_file.main_code)?;
+
+    let mut code_files = vec![];
+    for (i, bank) in game_file.code_banks.iter().enumerate() {
+        let name = format!("code_{i}.bin");
+        write(&dir.join(&name), bank)?;
+        code_files.push(name);
+    }
+
+    let mut atlas_files = vec![];
+    for (i, bank) in game_file.atlases.iter().enumerate() {
+        let name = format!("atlas_{i}.bin");
+        write(&dir.join(&name), bank)?;
+        if let Some(palette) = &options.png_palette {
+            let png = AtlasFile::from_bank(bank).to_png(palette);
+            write(&dir.join(format!("atlas_{i}.png")), &png)?;
+        }
+        atlas_files.push(name);
+    }
+
+    let mut palette_files = vec![];
+    if let Some(palettes) = &game_file.palettes {
+        fs::create_dir_all(dir.join(PALETTES_DIR))
+            .map_err(|e| FileAccessError(e, "creating palettes dir"))?;
+        for named in palettes.palettes() {
+            //build names palettes after the file stem, so dots are allowed (`title.v2`)
+            if named.name.is_empty()
+                || named.name.contains(['/', '\\'])
+                || named.name.contains("..")
+            {
+                return Err(InvalidPalette(format!(
+                    "Palette name '{}' can't be used as a file name",
+                    named.name
+                )));
+            }
+            let name = format!("{}/{}.{}", PALETTES_DIR, named.name, PALETTE_EXT);
+            write(&dir.join(&name), &named.palette.as_bytes()?)?;
+            palette_files.push(name);
+        }
+    }
+
+    let icon_file = match &game_file.icon {
+        Some(icon) => {
+            let name = format!("{ICON_FILE}.{IMAGE_EXT}");
+            write(&dir.join(&name), &icon.as_bytes()?)?;
+            Some(name)
+        }
+        None => None,
+    };
+    let cover_file = match &game_file.cover {
+        Some(cover) => {
+            let name = format!("{COVER_FILE}.{IMAGE_EXT}");
+            write(&dir.join(&name), &cover.as_bytes()?)?;
+            Some(name)
+        }
+        None => None,
+    };
+
+    let manifest = Manifest {
+        id: header.id.clone(),
+        name: header.name.clone(),
+        author: header.author.clone(),
+        version: header.version.replace('$', "$$"),
+        build: header.build.into(),
+        main_code: String::from(MAIN_CODE_FILE),
+        min_maikor_version: header.min_maikor_version,
+        compiled_for_maikor_version: Some(header.compiled_for_maikor_version),
+        code_files,
+        atlas_files,
+        ram_banks: header.ram_bank_count,
+        palette_files,
+        metadata: game_file.metadata.clone(),
+        icon_file,
+        cover_file,
+        localizations: game_file.localizations.clone().unwrap_or_default(),
+        build_info: false,
+        profiles: BTreeMap::new(),
+    };
+    manifest.write_file(dir.join(options.manifest_format.file_name()))?;
+    Ok(manifest)
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<(), GameFileError> {
+    fs::write(path, bytes).map_err(|e| FileAccessError(e, "writing project file"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::build::build_from_manifest;
+    use crate::build_info::{BuildInfo, TOOL_NAME};
+    use crate::checksum::sha256_hex;
+    use crate::constants::mem::{ATLAS_BANK, CODE_BANK, MAIN_CODE};
+    use crate::icon::{GameImage, ICON_SIZE};
+    use crate::manifest_format::TOML_MANIFEST_FILE;
+    use crate::palette::Color;
+    use crate::palette_set::PaletteSet;
+    use crate::test_utils::TestDir;
+    use crate::GameFileHeader;
+
+    #[test]
+    fn extract_and_rebuild() {
+        let dir = TestDir::new("extract");
+        let header = GameFileHeader::new(
+            String::from("com.example.game"),
+            9,
+            3,
+            2,
+            1,
+            String::from("Game"),
+            String::from("1.0-$x"),
+            String::from("Author"),
+            2,
+            2,
+        );
+        let mut file = GameFile::new(
+            header,
+            [1; MAIN_CODE],
+            vec![[2; CODE_BANK], [3; CODE_BANK]],
+            vec![[4; ATLAS_BANK], [5; ATLAS_BANK]],
+        );
+        let mut palettes = PaletteSet::new();
+        palettes
+            .add(
+                String::from("day"),
+                Palette::new(None, [Color::new(1, 2, 3); 16]),
+            )
+            .unwrap();
+        palettes
+            .add(
+                String::from("title.v2"),
+                Palette::new(None, [Color::new(4, 5, 6); 16]),
+            )
+            .unwrap();
+        file.palettes = Some(palettes.clone());
+        file.icon = Some(
+            GameImage::new_rgba(
+                ICON_SIZE,
+                ICON_SIZE,
+                vec![9; ICON_SIZE as usize * ICON_SIZE as usize * 4],
+            )
+            .unwrap(),
+        );
+        file.build_info = Some(BuildInfo {
+            manifest_hash: sha256_hex(b"manifest"),
+            inputs: vec![],
+            tool: String::from(TOOL_NAME),
+            timestamp: 1_700_000_000,
+        });
+
+        let options = ExtractOptions {
+            png_palette: Some(Palette::new(None, [Color::new(0, 0, 0); 16])),
+            manifest_format: ManifestFormat::Toml,
+        };
+        let manifest = extract_project(&file, &dir, &options).unwrap();
+        assert_eq!(manifest.code_files, vec!["code_0.bin", "code_1.bin"]);
+        assert!(dir.join("atlas_1.png").exists());
+        assert_eq!(manifest.icon_file.as_deref(), Some("icon.mimg"));
+        assert_eq!(manifest.cover_file, None);
+        assert!(!manifest.build_info);
+        assert_eq!(manifest.version, "1.0-$$x");
+        assert_eq!(
+            manifest.palette_files,
+            vec!["palettes/day.mpal", "palettes/title.v2.mpal"]
+        );
+
+        let reloaded = Manifest::from_file(dir.join(TOML_MANIFEST_FILE))
+            .unwrap()
+            .resolve(None, |_| None)
+            .unwrap();
+        let rebuilt = build_from_manifest(&reloaded, &dir).unwrap().game_file;
+        file.build_info = None;
+        assert_eq!(rebuilt.as_bytes().unwrap(), file.as_bytes().unwrap());
+
+        palettes
+            .add(
+                String::from("../escape"),
+                Palette::new(None, [Color::new(0, 0, 0); 16]),
+            )
+            .unwrap();
+        file.palettes = Some(palettes);
+        assert!(matches!(
+            extract_project(&file, &dir, &options),
+            Err(InvalidPalette(_))
+        ));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::GameFileError;
-use crate::GameFileError::FileAccessError;
+use crate::GameFileError::{FileAccessError, InvalidUtf8};
 use std::convert::TryInto;
 use std::fs::File;
 use std::io;
@@ -14,7 +14,7 @@ pub trait ReaderExt: Read {
     fn read_string(&mut self, len: usize) -> Result<String, io::Error> {
         let mut bytes = vec![0; len];
         self.read_exact(&mut bytes)?;
-        Ok(String::from_utf8_lossy(&bytes).to_string())
+        String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
     }
 
     fn read_block(&mut self, len: usize) -> Result<Vec<u8>, io::Error> {
@@ -37,6 +37,19 @@ pub trait ReaderExt: Read {
         Ok(output)
     }
 
+    /// Returns `None` if the reader is at the end
+    fn read_optional_u8(&mut self) -> Result<Option<u8>, io::Error> {
+        let mut bytes = [0; 1];
+        loop {
+            match self.read(&mut bytes) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(bytes[0])),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
     fn read_u8(&mut self) -> Result<u8, io::Error> {
         let mut bytes = vec![0; 1];
         self.read_exact(&mut bytes)?;
@@ -54,6 +67,12 @@ pub trait ReaderExt: Read {
         self.read_exact(&mut bytes)?;
         Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
     }
+
+    fn read_u64(&mut self) -> Result<u64, io::Error> {
+        let mut bytes = [0; 8];
+        self.read_exact(&mut bytes)?;
+        Ok(u64::from_be_bytes(bytes))
+    }
 }
 
 pub fn read_sized_blocks<R: ReaderExt, const N: usize>(
@@ -70,6 +89,21 @@ pub fn read_sized_blocks<R: ReaderExt, const N: usize>(
     Ok(banks)
 }
 
+/// Read length prefixed string, invalid UTF-8 is reported as [InvalidUtf8] rather than replaced
+pub fn read_header_string<R: ReaderExt>(
+    reader: &mut R,
+    field_name: &'static str,
+    context: &'static str,
+) -> Result<String, GameFileError> {
+    reader.read_len_string().map_err(|e| {
+        if e.kind() == io::ErrorKind::InvalidData {
+            InvalidUtf8(field_name)
+        } else {
+            FileAccessError(e, context)
+        }
+    })
+}
+
 pub fn convert_vec<T, const N: usize>(v: Vec<T>) -> [T; N] {
     v.try_into().unwrap_or_else(|v: Vec<T>| {
         panic!(
@@ -1,8 +1,15 @@
+use crate::build_info::BuildInfo;
 use crate::constants::mem::*;
 use crate::file_utils::{convert_vec, read_sized_blocks, ReaderExt};
+use crate::icon::GameImage;
+use crate::localization::Localizations;
+use crate::metadata::GameMetadata;
+use crate::palette_set::PaletteSet;
 use crate::read_write_impl::{Readable, Writeable};
-use crate::GameFileError::FileAccessError;
-use crate::{GameFile, GameFileError, GameFileHeader};
+use crate::section::{read_sections, sections_too_large, write_section, SectionId};
+use crate::GameFileError::{FileAccessError, ValidationFailed};
+use crate::{GameFile, GameFileError, GameFileHeader, MAX_SECTIONS_SIZE};
+use std::io::BufReader;
 
 impl GameFile {
     pub fn new(
@@ -16,6 +23,93 @@ impl GameFile {
             main_code,
             code_banks,
             atlases,
+            palettes: None,
+            metadata: None,
+            icon: None,
+            cover: None,
+            localizations: None,
+            build_info: None,
+        }
+    }
+}
+
+impl GameFile {
+    /// Validates header and that the content matches the header
+    pub fn validate(&self) -> Result<(), String> {
+        let mut error = match self.header.validate() {
+            Ok(_) => String::new(),
+            Err(header_error) => header_error,
+        };
+
+        if self.code_banks.len() != self.header.code_bank_count as usize {
+            error.push_str(&format!(
+                "Header has {} code banks but file has {}\n",
+                self.header.code_bank_count,
+                self.code_banks.len()
+            ));
+        }
+        if self.atlases.len() != self.header.atlas_bank_count as usize {
+            error.push_str(&format!(
+                "Header has {} atlas banks but file has {}\n",
+                self.header.atlas_bank_count,
+                self.atlases.len()
+            ));
+        }
+        if let Some(palettes) = &self.palettes {
+            if let Err(palette_errors) = palettes.validate() {
+                for palette_error in palette_errors {
+                    error.push_str(&palette_error);
+                    error.push('\n');
+                }
+            }
+        }
+
+        if let Some(metadata) = &self.metadata {
+            if let Err(metadata_errors) = metadata.validate() {
+                for metadata_error in metadata_errors {
+                    error.push_str(&metadata_error);
+                    error.push('\n');
+                }
+            }
+        }
+
+        if let Some(icon) = &self.icon {
+            if let Err(icon_error) = icon.validate_icon() {
+                error.push_str(&icon_error);
+                error.push('\n');
+            }
+        }
+        if let Some(cover) = &self.cover {
+            if let Err(cover_error) = cover.validate_cover() {
+                error.push_str(&cover_error);
+                error.push('\n');
+            }
+        }
+        if let Some(localizations) = &self.localizations {
+            if let Err(localization_errors) = localizations.validate() {
+                for localization_error in localization_errors {
+                    error.push_str(&localization_error);
+                    error.push('\n');
+                }
+            }
+        }
+        if let Some(build_info) = &self.build_info {
+            if let Err(build_info_errors) = build_info.validate() {
+                for build_info_error in build_info_errors {
+                    error.push_str(&build_info_error);
+                    error.push('\n');
+                }
+            }
+        }
+        if let Err(section_error) = self.sections_as_bytes() {
+            error.push_str(&section_error.to_string());
+            error.push('\n');
+        }
+
+        if error.is_empty() {
+            Ok(())
+        } else {
+            Err(error)
         }
     }
 }
@@ -24,16 +118,36 @@ impl Readable for GameFile {
     fn from_reader<R: ReaderExt>(reader: &mut R) -> Result<GameFile, GameFileError> {
         let header = GameFileHeader::from_reader(reader)?;
         let main_code = reader
-            .read_block(CODE_BANK)
+            .read_block(MAIN_CODE)
             .map_err(|e| FileAccessError(e, "reading main code"))?;
         let code_banks = read_sized_blocks(reader, header.code_bank_count as usize)?;
         let atlas_banks = read_sized_blocks(reader, header.atlas_bank_count as usize)?;
-        Ok(GameFile::new(
-            header,
-            convert_vec(main_code),
-            code_banks,
-            atlas_banks,
-        ))
+        let mut file = GameFile::new(header, convert_vec(main_code), code_banks, atlas_banks);
+        for (id, data) in read_sections(reader)? {
+            match SectionId::from_u8(id) {
+                Some(SectionId::Palettes) => {
+                    file.palettes = Some(PaletteSet::from_reader(&mut BufReader::new(&*data))?);
+                }
+                Some(SectionId::Metadata) => {
+                    file.metadata = Some(GameMetadata::from_reader(&mut BufReader::new(&*data))?);
+                }
+                Some(SectionId::Icon) => {
+                    file.icon = Some(GameImage::from_reader(&mut BufReader::new(&*data))?);
+                }
+                Some(SectionId::Cover) => {
+                    file.cover = Some(GameImage::from_reader(&mut BufReader::new(&*data))?);
+                }
+                Some(SectionId::Localizations) => {
+                    file.localizations =
+                        Some(Localizations::from_reader(&mut BufReader::new(&*data))?);
+                }
+                Some(SectionId::BuildInfo) => {
+                    file.build_info = Some(BuildInfo::from_reader(&mut BufReader::new(&*data))?);
+                }
+                None => {}
+            }
+        }
+        Ok(file)
     }
 }
 
@@ -47,15 +161,66 @@ impl Writeable for GameFile {
         for bank in &self.atlases {
             output.extend_from_slice(bank);
         }
+        output.extend_from_slice(&self.sections_as_bytes()?);
+
+        Ok(output)
+    }
+}
 
+impl GameFile {
+    /// Optional sections, as written after the atlas banks
+    ///
+    /// Fails if the sections are larger than the reader accepts
+    pub(crate) fn sections_as_bytes(&self) -> Result<Vec<u8>, GameFileError> {
+        let mut sections = vec![];
+        if let Some(palettes) = &self.palettes {
+            sections.push((SectionId::Palettes, palettes.as_bytes()?));
+        }
+        if let Some(metadata) = &self.metadata {
+            sections.push((SectionId::Metadata, metadata.as_bytes()?));
+        }
+        if let Some(icon) = &self.icon {
+            sections.push((SectionId::Icon, icon.as_bytes()?));
+        }
+        if let Some(cover) = &self.cover {
+            sections.push((SectionId::Cover, cover.as_bytes()?));
+        }
+        if let Some(localizations) = &self.localizations {
+            sections.push((SectionId::Localizations, localizations.as_bytes()?));
+        }
+        if let Some(build_info) = &self.build_info {
+            sections.push((SectionId::BuildInfo, build_info.as_bytes()?));
+        }
+        let mut output = vec![];
+        let mut total = 0;
+        for (id, data) in sections {
+            total += data.len() as u64;
+            if total > MAX_SECTIONS_SIZE {
+                return Err(sections_too_large(id as u8));
+            }
+            write_section(&mut output, id, &data);
+        }
         Ok(output)
     }
+
+    /// Parse and validate a game file produced by applying a patch
+    pub(crate) fn from_patched_bytes(bytes: &[u8]) -> Result<GameFile, GameFileError> {
+        let file = GameFile::from_reader(&mut BufReader::new(bytes))?;
+        file.validate().map_err(ValidationFailed)?;
+        Ok(file)
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::read_write_impl::Writeable;
-    use crate::{GameFile, GameFileHeader, ATLAS_BANK, CODE_BANK, MAIN_CODE};
+    use crate::icon::{GameImage, MAX_COVER_HEIGHT, MAX_COVER_WIDTH};
+    use crate::metadata::GameMetadata;
+    use crate::palette::{Color, Palette};
+    use crate::palette_set::PaletteSet;
+    use crate::read_write_impl::{Readable, Writeable};
+    use crate::GameFileError::InvalidSection;
+    use crate::{GameFile, GameFileHeader, ATLAS_BANK, CODE_BANK, MAIN_CODE, MAX_SECTIONS_SIZE};
+    use std::io::BufReader;
 
     #[test]
     #[rustfmt::skip]
@@ -66,4 +231,68 @@ mod test {
         let bytes  = file.as_bytes().unwrap();
         assert_eq!(bytes.len(), MAIN_CODE + CODE_BANK + ATLAS_BANK + 22);
     }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_read_write_palettes() {
+        let header = GameFileHeader::new(String::from("1"), 1, 1, 1,0, String::from("a"), String::from("b"), String::from("c"), 1, 1);
+        let mut file = GameFile::new(header, [1; MAIN_CODE], vec![[2; CODE_BANK]], vec![[3; ATLAS_BANK]]);
+        let mut palettes = PaletteSet::new();
+        palettes.add(String::from("main"), Palette::new(None, [Color::new(9, 8, 7); 16])).unwrap();
+        file.palettes = Some(palettes.clone());
+
+        let bytes = file.as_bytes().unwrap();
+        let parsed = GameFile::from_reader(&mut BufReader::new(&*bytes)).unwrap();
+        assert_eq!(parsed.header, file.header);
+        assert_eq!(parsed.main_code, file.main_code);
+        assert_eq!(parsed.code_banks, file.code_banks);
+        assert_eq!(parsed.atlases, file.atlases);
+        assert_eq!(parsed.palettes, Some(palettes));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_read_write_metadata() {
+        let header = GameFileHeader::new(String::from("com.test.app"), 1, 1, 1,0, String::from("a"), String::from("b"), String::from("c"), 1, 1);
+        let mut file = GameFile::new(header, [1; MAIN_CODE], vec![[2; CODE_BANK]], vec![[3; ATLAS_BANK]]);
+        let metadata = GameMetadata {
+            description: Some(String::from("Description")),
+            license: Some(String::from("MIT")),
+            ..GameMetadata::default()
+        };
+        file.metadata = Some(metadata.clone());
+
+        let bytes = file.as_bytes().unwrap();
+        let parsed = GameFile::from_reader(&mut BufReader::new(&*bytes)).unwrap();
+        assert_eq!(parsed.metadata, Some(metadata));
+        //header only reads stop before the sections
+        let header = GameFileHeader::from_reader(&mut BufReader::new(&*bytes)).unwrap();
+        assert_eq!(header, file.header);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_validate() {
+        let header = GameFileHeader::new(String::from("com.test.app"), 1, 1, 1,0, String::from("a"), String::from("b"), String::from("c"), 1, 2);
+        let file = GameFile::new(header, [1; MAIN_CODE], vec![[2; CODE_BANK]], vec![[3; ATLAS_BANK]]);
+
+        assert_eq!(file.validate(), Err(String::from("Header has 2 atlas banks but file has 1\n")));
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn test_sections_too_large() {
+        let header = GameFileHeader::new(String::from("com.test.app"), 1, 1, 1,0, String::from("a"), String::from("b"), String::from("c"), 0, 1);
+        let mut file = GameFile::new(header, [1; MAIN_CODE], vec![], vec![[3; ATLAS_BANK]]);
+        file.cover = Some(GameImage::new_rgba(MAX_COVER_WIDTH, MAX_COVER_HEIGHT, vec![255; MAX_COVER_WIDTH as usize * MAX_COVER_HEIGHT as usize * 4]).unwrap());
+        assert_eq!(file.validate(), Ok(()));
+        let mut metadata = GameMetadata::default();
+        for i in 0..12 {
+            metadata.custom.insert(format!("key{i}"), "a".repeat(u16::MAX as usize));
+        }
+        file.metadata = Some(metadata);
+
+        assert!(matches!(file.as_bytes(), Err(InvalidSection(4, _))));
+        assert_eq!(file.validate(), Err(format!("Invalid section 4: sections are too large, max is {MAX_SECTIONS_SIZE} bytes\n")));
+    }
 }
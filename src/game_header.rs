@@ -1,4 +1,5 @@
-use crate::file_utils::ReaderExt;
+use crate::file_utils::{read_header_string, ReaderExt};
+use crate::game_id::validate_id;
 use crate::read_write_impl::{Readable, Writeable};
 use crate::GameFileError::{FieldTooLong, FileAccessError};
 use crate::{
@@ -48,22 +49,21 @@ impl GameFileHeader {
         if self.author.trim().is_empty() {
             error.push_str("Author must have at least one character\n");
         } else if self.author.trim().len() > MAX_STRING_LEN {
-            error.push_str("Author is too long, max of 255 characters\n");
+            error.push_str("Author is too long, max of 255 bytes\n");
         }
         if self.name.trim().is_empty() {
             error.push_str("Name must have at least one character\n");
         } else if self.name.trim().len() > MAX_STRING_LEN {
-            error.push_str("Name is too long, max of 255 characters\n");
+            error.push_str("Name is too long, max of 255 bytes\n");
         }
         if self.version.trim().is_empty() {
             error.push_str("Version must have at least one character\n");
         } else if self.version.trim().len() > MAX_STRING_LEN {
-            error.push_str("Version is too long, max of 255 characters\n");
+            error.push_str("Version is too long, max of 255 bytes\n");
         }
-        if self.id.trim().is_empty() {
-            error.push_str("ID must have at least one character\n");
-        } else if self.id.trim().len() > MAX_STRING_LEN {
-            error.push_str("ID is too long, max of 255 characters\n");
+        if let Err(id_error) = validate_id(&self.id) {
+            error.push_str(&id_error);
+            error.push('\n');
         }
         if self.atlas_bank_count == 0 {
             error.push_str("Must have at least one atlas bank\n");
@@ -101,18 +101,10 @@ impl Readable for GameFileHeader {
         let build = reader
             .read_u32()
             .map_err(|e| FileAccessError(e, "reading build"))?;
-        let id = reader
-            .read_len_string()
-            .map_err(|e| FileAccessError(e, "reading id"))?;
-        let name = reader
-            .read_len_string()
-            .map_err(|e| FileAccessError(e, "reading name"))?;
-        let version = reader
-            .read_len_string()
-            .map_err(|e| FileAccessError(e, "reading version"))?;
-        let author = reader
-            .read_len_string()
-            .map_err(|e| FileAccessError(e, "reading author"))?;
+        let id = read_header_string(reader, "ID", "reading id")?;
+        let name = read_header_string(reader, "Name", "reading name")?;
+        let version = read_header_string(reader, "Version", "reading version")?;
+        let author = read_header_string(reader, "Author", "reading author")?;
         let code_bank_count = reader
             .read_u8()
             .map_err(|e| FileAccessError(e, "reading code bank count"))?;
@@ -158,19 +150,29 @@ impl Writeable for GameFileHeader {
     }
 }
 
-fn convert_string(field_name: &'static str, str: &str) -> Result<Vec<u8>, GameFileError> {
-    let len = str.trim().len();
+/// Convert string to length prefixed bytes
+///
+/// Leading and trailing whitespace is removed, the limit is [MAX_STRING_LEN] bytes of UTF-8 (not
+/// characters) after trimming
+pub(crate) fn convert_string(
+    field_name: &'static str,
+    str: &str,
+) -> Result<Vec<u8>, GameFileError> {
+    let trimmed = str.trim();
+    let len = trimmed.len();
     if len > MAX_STRING_LEN {
         return Err(FieldTooLong(field_name, MAX_STRING_LEN, len));
     }
-    let mut bytes = str.as_bytes().to_vec();
-    bytes.insert(0, len as u8);
+    let mut bytes = Vec::with_capacity(len + 1);
+    bytes.push(len as u8);
+    bytes.extend_from_slice(trimmed.as_bytes());
     Ok(bytes)
 }
 
 #[cfg(test)]
 mod test {
     use crate::read_write_impl::{Readable, Writeable};
+    use crate::GameFileError::{FieldTooLong, InvalidUtf8};
     use crate::GameFileHeader;
     use std::io::BufReader;
 
@@ -271,4 +273,82 @@ mod test {
 
         assert_eq!(header, parsed_header);
     }
+
+    #[test]
+    fn test_multibyte_strings() {
+        let header = GameFileHeader::new(
+            String::from("com.example.game"),
+            1,
+            1,
+            1,
+            0,
+            String::from("ゲーム"),
+            String::from("1.0"),
+            String::from("Zoë Ångström"),
+            0,
+            1,
+        );
+        let bytes = header.as_bytes().unwrap();
+        //name is 3 characters but 9 bytes
+        let name_start = 11 + 1 + header.id.len();
+        assert_eq!(bytes[name_start], 9);
+
+        let parsed = GameFileHeader::from_reader(&mut BufReader::new(&*bytes)).unwrap();
+        assert_eq!(parsed, header);
+    }
+
+    #[test]
+    fn test_string_trimming() {
+        let mut header = GameFileHeader::new(
+            String::from("com.example.game"),
+            1,
+            1,
+            1,
+            0,
+            String::from("  Game  "),
+            String::from("1.0 "),
+            String::from("Author"),
+            0,
+            1,
+        );
+        let bytes = header.as_bytes().unwrap();
+        let parsed = GameFileHeader::from_reader(&mut BufReader::new(&*bytes)).unwrap();
+        assert_eq!(parsed.name, "Game");
+        assert_eq!(parsed.version, "1.0");
+        assert_eq!(parsed.author, "Author");
+
+        //limit is in bytes, 85 * 3 = 255
+        header.name = "語".repeat(85);
+        assert!(header.as_bytes().is_ok());
+        assert!(header.validate().is_ok());
+        header.name = "語".repeat(86);
+        assert!(matches!(
+            header.as_bytes(),
+            Err(FieldTooLong("Name", 255, 258))
+        ));
+        assert!(header.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let header = GameFileHeader::new(
+            String::from("com.example.game"),
+            1,
+            1,
+            1,
+            0,
+            String::from("Game"),
+            String::from("1.0"),
+            String::from("Author"),
+            0,
+            1,
+        );
+        let mut bytes = header.as_bytes().unwrap();
+        let name_start = 11 + 1 + header.id.len() + 1;
+        bytes[name_start] = 0xFF;
+        assert!(matches!(
+            GameFileHeader::from_reader(&mut BufReader::new(&*bytes)),
+            Err(InvalidUtf8("Name"))
+        ));
+    }
 }
@@ -0,0 +1,162 @@
+use crate::GameFileError::InvalidGameId;
+use crate::{GameFileError, GameFileHeader, MAX_STRING_LEN};
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Prefixes that can't be used by games, `app.maikor` is used by the system
+pub const RESERVED_ID_PREFIXES: &[&str] = &["app.maikor"];
+const MIN_SEGMENTS: usize = 2;
+const MAX_SEGMENT_LEN: usize = 63;
+
+/// Unique game ID in reverse domain format, i.e. `com.example.game`
+///
+/// IDs are normalized to lowercase when parsed. Each segment must start with a letter and only
+/// contain `a-z`, `0-9` and `_`
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
+pub struct GameId(String);
+
+impl GameId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.0.split('.')
+    }
+}
+
+impl FromStr for GameId {
+    type Err = GameFileError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = |reason: String| InvalidGameId(format!("'{text}' {reason}"));
+        let id = text.trim().to_ascii_lowercase();
+        if id.is_empty() {
+            return Err(error(String::from("is empty")));
+        }
+        if id.len() > MAX_STRING_LEN {
+            return Err(error(format!("is too long, max of {MAX_STRING_LEN} bytes")));
+        }
+        let segments: Vec<&str> = id.split('.').collect();
+        if segments.len() < MIN_SEGMENTS {
+            return Err(error(String::from(
+                "must be in reverse domain format, i.e. com.example.game",
+            )));
+        }
+        for segment in &segments {
+            if segment.is_empty() {
+                return Err(error(String::from("has an empty segment")));
+            }
+            if segment.len() > MAX_SEGMENT_LEN {
+                return Err(error(format!(
+                    "has segment '{segment}' longer than {MAX_SEGMENT_LEN} characters"
+                )));
+            }
+            if !segment.starts_with(|c: char| c.is_ascii_lowercase()) {
+                return Err(error(format!(
+                    "has segment '{segment}' that doesn't start with a letter"
+                )));
+            }
+            if let Some(c) = segment
+                .chars()
+                .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '_'))
+            {
+                return Err(error(format!(
+                    "contains '{c}', only a-z, 0-9, _ and . are allowed"
+                )));
+            }
+        }
+        if let Some(prefix) = RESERVED_ID_PREFIXES
+            .iter()
+            .find(|prefix| id == **prefix || id.starts_with(&format!("{prefix}.")))
+        {
+            return Err(error(format!("uses reserved prefix '{prefix}'")));
+        }
+        Ok(GameId(id))
+    }
+}
+
+impl Display for GameId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl AsRef<str> for GameId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<GameId> for String {
+    fn from(id: GameId) -> Self {
+        id.0
+    }
+}
+
+impl GameFileHeader {
+    /// Parsed and normalized ID
+    pub fn game_id(&self) -> Result<GameId, GameFileError> {
+        self.id.parse()
+    }
+}
+
+/// Check `id` is valid and already normalized, as required for game files
+pub(crate) fn validate_id(id: &str) -> Result<(), String> {
+    match id.parse::<GameId>() {
+        Ok(game_id) if game_id.as_str() == id => Ok(()),
+        Ok(game_id) => Err(format!("ID '{id}' must be normalized as '{game_id}'")),
+        Err(InvalidGameId(msg)) => Err(format!("ID {msg}")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let id: GameId = "com.example.game".parse().unwrap();
+        assert_eq!(id.as_str(), "com.example.game");
+        assert_eq!(id.segments().count(), 3);
+        let id: GameId = " Com.Example.My_Game2 ".parse().unwrap();
+        assert_eq!(id.to_string(), "com.example.my_game2");
+
+        for invalid in [
+            "",
+            "game",
+            "com..game",
+            "com.example.",
+            ".com.example",
+            "com.2fast",
+            "com.example.my-game",
+            "com.example.gäme",
+            "com.example game",
+            "app.maikor",
+            "app.maikor.settings",
+        ] {
+            assert!(invalid.parse::<GameId>().is_err(), "{invalid}");
+        }
+        assert!("app.maikorfan.game".parse::<GameId>().is_ok());
+        assert!(format!("com.{}", "a".repeat(64)).parse::<GameId>().is_err());
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(validate_id("com.example.game"), Ok(()));
+        assert_eq!(
+            validate_id("Com.Example"),
+            Err(String::from(
+                "ID 'Com.Example' must be normalized as 'com.example'"
+            ))
+        );
+        assert_eq!(
+            validate_id("game"),
+            Err(String::from(
+                "ID 'game' must be in reverse domain format, i.e. com.example.game"
+            ))
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use crate::GameFileError::InvalidGameVersion;
+use crate::{GameFileError, GameFileHeader};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Semantic version parsed from [GameFileHeader::version]
+///
+/// Accepts `MAJOR[.MINOR[.PATCH]][-PRERELEASE][+BUILD]` with an optional leading `v`,
+/// missing parts are treated as 0. Build metadata is kept but ignored when comparing.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct GameVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    ///Pre-release identifiers, i.e. `["beta", "2"]` for `1.0.0-beta.2`
+    pub pre_release: Vec<String>,
+    ///Build metadata, i.e. `"abc123"` for `1.0.0+abc123`
+    pub metadata: Option<String>,
+}
+
+impl GameVersion {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            pre_release: vec![],
+            metadata: None,
+        }
+    }
+
+    pub fn is_pre_release(&self) -> bool {
+        !self.pre_release.is_empty()
+    }
+}
+
+impl FromStr for GameVersion {
+    type Err = GameFileError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = |reason: &str| InvalidGameVersion(format!("'{text}' {reason}"));
+        let trimmed = text.trim();
+        let trimmed = trimmed
+            .strip_prefix('v')
+            .or_else(|| trimmed.strip_prefix('V'))
+            .unwrap_or(trimmed);
+        let (rest, metadata) = match trimmed.split_once('+') {
+            Some((rest, metadata)) => (rest, Some(metadata)),
+            None => (trimmed, None),
+        };
+        let (core, pre_release) = match rest.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (rest, None),
+        };
+
+        let parts: Vec<&str> = core.split('.').collect();
+        if parts.len() > 3 {
+            return Err(error("has more than 3 version numbers"));
+        }
+        let mut numbers = [0; 3];
+        for (number, part) in numbers.iter_mut().zip(parts) {
+            if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+                return Err(error("must start with MAJOR.MINOR.PATCH numbers"));
+            }
+            *number = part
+                .parse()
+                .map_err(|_| error("has a version number that is too large"))?;
+        }
+
+        let pre_release = match pre_release {
+            Some(pre) => {
+                let identifiers: Vec<String> = pre.split('.').map(String::from).collect();
+                if identifiers.iter().any(|id| !is_identifier(id)) {
+                    return Err(error("has an invalid pre-release"));
+                }
+                if identifiers.iter().any(|id| has_leading_zero(id)) {
+                    return Err(error("has a pre-release number with leading zeros"));
+                }
+                identifiers
+            }
+            None => vec![],
+        };
+        if let Some(metadata) = metadata {
+            if metadata.split('.').any(|id| !is_identifier(id)) {
+                return Err(error("has invalid build metadata"));
+            }
+        }
+
+        Ok(GameVersion {
+            major: numbers[0],
+            minor: numbers[1],
+            patch: numbers[2],
+            pre_release,
+            metadata: metadata.map(String::from),
+        })
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+///Numeric pre-release identifiers can't have leading zeros (`01`), alphanumeric ones (`01a`) can
+fn has_leading_zero(text: &str) -> bool {
+    text.len() > 1 && text.starts_with('0') && text.chars().all(|c| c.is_ascii_digit())
+}
+
+impl Display for GameVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if self.is_pre_release() {
+            write!(f, "-{}", self.pre_release.join("."))?;
+        }
+        if let Some(metadata) = &self.metadata {
+            write!(f, "+{metadata}")?;
+        }
+        Ok(())
+    }
+}
+
+impl PartialOrd for GameVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Ordered by semver precedence, then by metadata so that the ordering is consistent with `Eq`
+impl Ord for GameVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.precedence(other)
+            .then_with(|| self.metadata.cmp(&other.metadata))
+    }
+}
+
+impl GameVersion {
+    /// Compare using semver precedence rules, build metadata is ignored
+    pub fn precedence(&self, other: &GameVersion) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| {
+                match (self.is_pre_release(), other.is_pre_release()) {
+                    (false, false) => Ordering::Equal,
+                    //releases are newer than their pre-releases
+                    (false, true) => Ordering::Greater,
+                    (true, false) => Ordering::Less,
+                    (true, true) => compare_pre_release(&self.pre_release, &other.pre_release),
+                }
+            })
+    }
+}
+
+fn compare_pre_release(lhs: &[String], rhs: &[String]) -> Ordering {
+    for (lhs, rhs) in lhs.iter().zip(rhs) {
+        let ordering = match (lhs.parse::<u64>(), rhs.parse::<u64>()) {
+            (Ok(lhs), Ok(rhs)) => lhs.cmp(&rhs),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => lhs.cmp(rhs),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    lhs.len().cmp(&rhs.len())
+}
+
+/// Result of [GameFileHeader::is_update_of]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UpdateCheck {
+    ///True if the header can replace the installed header
+    pub is_update: bool,
+    ///Why the header is or isn't an update, in the order checked
+    pub reasons: Vec<String>,
+}
+
+impl GameFileHeader {
+    /// Game version parsed as a semantic version, `None` if it isn't in semver format
+    pub fn semantic_version(&self) -> Option<GameVersion> {
+        self.version.parse().ok()
+    }
+
+    /// Compare game versions, `None` if either isn't in semver format
+    pub fn compare_version(&self, other: &GameFileHeader) -> Option<Ordering> {
+        Some(
+            self.semantic_version()?
+                .precedence(&other.semantic_version()?),
+        )
+    }
+
+    /// Check if this header is a valid upgrade of `installed`
+    ///
+    /// The ID must match and the build must be higher, if both versions are semantic versions
+    /// then the version must not go down
+    pub fn is_update_of(&self, installed: &GameFileHeader) -> UpdateCheck {
+        let mut is_update = true;
+        let mut reasons = vec![];
+
+        if self.id == installed.id {
+            reasons.push(format!("ID matches ({})", self.id));
+        } else {
+            is_update = false;
+            reasons.push(format!(
+                "ID is different ({} vs installed {})",
+                self.id, installed.id
+            ));
+        }
+
+        match self.build.cmp(&installed.build) {
+            Ordering::Greater => reasons.push(format!(
+                "Build {} is newer than installed {}",
+                self.build, installed.build
+            )),
+            Ordering::Equal => {
+                is_update = false;
+                reasons.push(format!("Build {} is already installed", self.build));
+            }
+            Ordering::Less => {
+                is_update = false;
+                reasons.push(format!(
+                    "Build {} is older than installed {}",
+                    self.build, installed.build
+                ));
+            }
+        }
+
+        match (self.semantic_version(), installed.semantic_version()) {
+            (Some(version), Some(installed_version)) => {
+                match version.precedence(&installed_version) {
+                    Ordering::Greater => reasons.push(format!(
+                        "Version {version} is newer than installed {installed_version}"
+                    )),
+                    Ordering::Equal => reasons.push(format!(
+                        "Version {version} is the same as installed {installed_version}"
+                    )),
+                    Ordering::Less => {
+                        is_update = false;
+                        reasons.push(format!(
+                            "Version {version} is older than installed {installed_version}"
+                        ));
+                    }
+                }
+            }
+            _ => reasons.push(format!(
+                "Versions '{}' and '{}' can't be compared, only build is used",
+                self.version, installed.version
+            )),
+        }
+
+        UpdateCheck { is_update, reasons }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn version(text: &str) -> GameVersion {
+        text.parse().unwrap()
+    }
+
+    fn header(id: &str, build: u32, version: &str) -> GameFileHeader {
+        GameFileHeader::new(
+            String::from(id),
+            build,
+            1,
+            1,
+            0,
+            String::from("Game"),
+            String::from(version),
+            String::from("Author"),
+            0,
+            1,
+        )
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(version("1.2.3"), GameVersion::new(1, 2, 3));
+        assert_eq!(version("v2"), GameVersion::new(2, 0, 0));
+        assert_eq!(version(" 1.4 "), GameVersion::new(1, 4, 0));
+        let full = version("1.0.0-beta.2+abc-1");
+        assert_eq!(full.pre_release, vec!["beta", "2"]);
+        assert_eq!(full.metadata.as_deref(), Some("abc-1"));
+        assert_eq!(full.to_string(), "1.0.0-beta.2+abc-1");
+        assert_eq!(version("1.0.0-0.01a+001").pre_release, vec!["0", "01a"]);
+
+        for invalid in [
+            "",
+            "1.2.3.4",
+            "one",
+            "1..2",
+            "1.0-",
+            "1.0-a..b",
+            "1.0+",
+            "1.a",
+            "1.0.0-01",
+            "1.0.0-beta.007",
+        ] {
+            assert!(invalid.parse::<GameVersion>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn ordering() {
+        let ordered = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.0.1",
+            "1.1.0",
+            "2.0.0",
+        ];
+        for pair in ordered.windows(2) {
+            assert!(version(pair[0]) < version(pair[1]), "{pair:?}");
+        }
+        assert_eq!(
+            version("1.0.0+a").precedence(&version("1.0.0+b")),
+            Ordering::Equal
+        );
+        assert_eq!(
+            header("a", 1, "1.2").compare_version(&header("a", 1, "1.10")),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            header("a", 1, "first").compare_version(&header("a", 1, "1.0")),
+            None
+        );
+    }
+
+    #[test]
+    fn update() {
+        let installed = header("com.example.game", 5, "1.2.0");
+
+        let check = header("com.example.game", 6, "1.3.0").is_update_of(&installed);
+        assert!(check.is_update);
+        assert_eq!(check.reasons.len(), 3);
+
+        let check = header("com.example.other", 6, "1.3.0").is_update_of(&installed);
+        assert!(!check.is_update);
+        assert!(check.reasons[0].contains("ID is different"));
+
+        assert!(
+            !header("com.example.game", 5, "1.3.0")
+                .is_update_of(&installed)
+                .is_update
+        );
+        assert!(
+            !header("com.example.game", 4, "1.3.0")
+                .is_update_of(&installed)
+                .is_update
+        );
+
+        let check = header("com.example.game", 6, "1.1.0").is_update_of(&installed);
+        assert!(!check.is_update);
+        assert!(check.reasons[2].contains("older"));
+
+        let check = header("com.example.game", 6, "nightly").is_update_of(&installed);
+        assert!(check.is_update);
+        assert!(check.reasons[2].contains("can't be compared"));
+    }
+}
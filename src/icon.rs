@@ -0,0 +1,371 @@
+use crate::constants::mem::{ATLAS_BANK, CODE_BANK, MAIN_CODE};
+use crate::file_utils::{convert_vec, ReaderExt};
+use crate::palette::{Color, Palette};
+use crate::read_write_impl::{validate_file, FileReadable, Readable, Writeable};
+use crate::section::{sections_too_large, SectionId};
+use crate::GameFileError::{FileAccessError, InvalidImage};
+use crate::{GameFile, GameFileError, GameFileHeader, MAX_SECTIONS_SIZE};
+use std::fs::File;
+use std::io::{BufReader, Seek};
+use std::path::Path;
+
+const IMAGE_HEADER: [u8; 2] = [0xFD, 0xA5];
+pub const IMAGE_EXT: &str = "mimg";
+///Width and height of game icons
+pub const ICON_SIZE: u16 = 32;
+pub const MAX_COVER_WIDTH: u16 = 320;
+pub const MAX_COVER_HEIGHT: u16 = 240;
+const FORMAT_INDEXED: u8 = 1;
+const FORMAT_RGBA: u8 = 2;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ImagePixels {
+    ///One palette index (0-15) per pixel, index 0 is transparent
+    Indexed { palette: Palette, pixels: Vec<u8> },
+    ///Four bytes (RGBA) per pixel
+    Rgba(Vec<u8>),
+}
+
+/// Image used for game icons and covers
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GameImage {
+    pub width: u16,
+    pub height: u16,
+    pub pixels: ImagePixels,
+}
+
+impl GameImage {
+    pub fn new_indexed(
+        width: u16,
+        height: u16,
+        palette: Palette,
+        pixels: Vec<u8>,
+    ) -> Result<Self, GameFileError> {
+        let image = Self {
+            width,
+            height,
+            pixels: ImagePixels::Indexed { palette, pixels },
+        };
+        image.check_pixels()?;
+        Ok(image)
+    }
+
+    pub fn new_rgba(width: u16, height: u16, rgba: Vec<u8>) -> Result<Self, GameFileError> {
+        let image = Self {
+            width,
+            height,
+            pixels: ImagePixels::Rgba(rgba),
+        };
+        image.check_pixels()?;
+        Ok(image)
+    }
+
+    fn pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    fn check_pixels(&self) -> Result<(), GameFileError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(InvalidImage(String::from("Image must not be empty")));
+        }
+        let (len, expected) = match &self.pixels {
+            ImagePixels::Indexed { pixels, .. } => {
+                if let Some(index) = pixels.iter().find(|index| **index > 15) {
+                    return Err(InvalidImage(format!(
+                        "Palette index must be 0-15 but was {index}"
+                    )));
+                }
+                (pixels.len(), self.pixel_count())
+            }
+            ImagePixels::Rgba(rgba) => (rgba.len(), self.pixel_count() * 4),
+        };
+        if len != expected {
+            return Err(InvalidImage(format!(
+                "{}x{} image needs {expected} bytes of pixel data but had {len}",
+                self.width, self.height
+            )));
+        }
+        Ok(())
+    }
+
+    /// RGBA value for pixel at `x`, `y`
+    pub fn rgba_at(&self, x: u16, y: u16) -> [u8; 4] {
+        let idx = y as usize * self.width as usize + x as usize;
+        match &self.pixels {
+            ImagePixels::Indexed { palette, pixels } => match pixels[idx] {
+                0 => [0, 0, 0, 0],
+                index => {
+                    let color = palette.colors[index as usize];
+                    [color.r, color.g, color.b, 255]
+                }
+            },
+            ImagePixels::Rgba(rgba) => convert_vec(rgba[idx * 4..idx * 4 + 4].to_vec()),
+        }
+    }
+
+    /// Convert to RGBA pixel data, four bytes per pixel
+    pub fn to_rgba(&self) -> Vec<u8> {
+        match &self.pixels {
+            ImagePixels::Rgba(rgba) => rgba.clone(),
+            ImagePixels::Indexed { .. } => (0..self.height)
+                .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+                .flat_map(|(x, y)| self.rgba_at(x, y))
+                .collect(),
+        }
+    }
+
+    /// Check image can be used as a game icon
+    pub fn validate_icon(&self) -> Result<(), String> {
+        if self.width != ICON_SIZE || self.height != ICON_SIZE {
+            return Err(format!(
+                "Icon must be {ICON_SIZE}x{ICON_SIZE} but was {}x{}",
+                self.width, self.height
+            ));
+        }
+        self.check_pixels().map_err(|e| e.to_string())
+    }
+
+    /// Check image can be used as a game cover
+    pub fn validate_cover(&self) -> Result<(), String> {
+        if self.width > MAX_COVER_WIDTH || self.height > MAX_COVER_HEIGHT {
+            return Err(format!(
+                "Cover must be at most {MAX_COVER_WIDTH}x{MAX_COVER_HEIGHT} but was {}x{}",
+                self.width, self.height
+            ));
+        }
+        self.check_pixels().map_err(|e| e.to_string())
+    }
+}
+
+impl Writeable for GameImage {
+    fn as_bytes(&self) -> Result<Vec<u8>, GameFileError> {
+        self.check_pixels()?;
+        let mut output = vec![];
+        output.extend_from_slice(&IMAGE_HEADER);
+        output.push(match self.pixels {
+            ImagePixels::Indexed { .. } => FORMAT_INDEXED,
+            ImagePixels::Rgba(_) => FORMAT_RGBA,
+        });
+        output.extend_from_slice(&self.width.to_be_bytes());
+        output.extend_from_slice(&self.height.to_be_bytes());
+        match &self.pixels {
+            ImagePixels::Indexed { palette, pixels } => {
+                for color in palette.colors {
+                    output.extend_from_slice(&color.as_bytes());
+                }
+                //two pixels per byte, left pixel in the high nibble (same as atlases)
+                for pair in pixels.chunks(2) {
+                    output.push(pair[0] << 4 | pair.get(1).copied().unwrap_or_default());
+                }
+            }
+            ImagePixels::Rgba(rgba) => output.extend_from_slice(rgba),
+        }
+        Ok(output)
+    }
+}
+
+impl Readable for GameImage {
+    fn from_reader<R: ReaderExt>(reader: &mut R) -> Result<Self, GameFileError>
+    where
+        Self: Sized,
+    {
+        let mut header = [0; 2];
+        reader
+            .read_exact(&mut header)
+            .map_err(|e| FileAccessError(e, "reading image header"))?;
+        if header != IMAGE_HEADER {
+            return Err(InvalidImage(String::from("Not an image file")));
+        }
+        let format = reader
+            .read_u8()
+            .map_err(|e| FileAccessError(e, "reading image format"))?;
+        let width = reader
+            .read_u16()
+            .map_err(|e| FileAccessError(e, "reading image width"))?;
+        let height = reader
+            .read_u16()
+            .map_err(|e| FileAccessError(e, "reading image height"))?;
+        let pixel_count = width as usize * height as usize;
+        match format {
+            FORMAT_INDEXED => {
+                let colors: Vec<Color> = reader
+                    .read_multiple_blocks(3, 16)
+                    .map_err(|e| FileAccessError(e, "reading image palette"))?
+                    .into_iter()
+                    .map(|rgb| Color::from_bytes(convert_vec(rgb)))
+                    .collect();
+                let packed = reader
+                    .read_block(pixel_count.div_ceil(2))
+                    .map_err(|e| FileAccessError(e, "reading image pixels"))?;
+                let mut pixels: Vec<u8> = packed
+                    .iter()
+                    .flat_map(|byte| [byte >> 4, byte & 0xF])
+                    .collect();
+                pixels.truncate(pixel_count);
+                GameImage::new_indexed(
+                    width,
+                    height,
+                    Palette::new(None, convert_vec(colors)),
+                    pixels,
+                )
+            }
+            FORMAT_RGBA => {
+                let rgba = reader
+                    .read_block(pixel_count * 4)
+                    .map_err(|e| FileAccessError(e, "reading image pixels"))?;
+                GameImage::new_rgba(width, height, rgba)
+            }
+            _ => Err(InvalidImage(format!("Unknown image format {format}"))),
+        }
+    }
+}
+
+impl FileReadable for GameImage {
+    const CHECK_SIZE: bool = false;
+}
+
+impl GameFile {
+    /// Read only the icon from a game file, banks and other sections are skipped without
+    /// being read
+    pub fn read_icon<P: AsRef<Path>>(path: P) -> Result<Option<GameImage>, GameFileError> {
+        let path = path.as_ref();
+        validate_file(path, true)?;
+        let file = File::open(path).map_err(|e| FileAccessError(e, "reading file"))?;
+        read_icon_from(&mut BufReader::new(file))
+    }
+}
+
+/// Read only the icon from a game file, the reader must be at the start of the file
+pub fn read_icon_from<R: ReaderExt + Seek>(
+    reader: &mut R,
+) -> Result<Option<GameImage>, GameFileError> {
+    let header = GameFileHeader::from_reader(reader)?;
+    let banks_len = MAIN_CODE
+        + header.code_bank_count as usize * CODE_BANK
+        + header.atlas_bank_count as usize * ATLAS_BANK;
+    reader
+        .seek_relative(banks_len as i64)
+        .map_err(|e| FileAccessError(e, "skipping banks"))?;
+    let mut total = 0;
+    while let Some(id) = reader
+        .read_optional_u8()
+        .map_err(|e| FileAccessError(e, "reading section id"))?
+    {
+        let len = reader
+            .read_u32()
+            .map_err(|e| FileAccessError(e, "reading section length"))?;
+        total += len as u64;
+        if total > MAX_SECTIONS_SIZE {
+            return Err(sections_too_large(id));
+        }
+        if id == SectionId::Icon as u8 {
+            let data = reader
+                .read_block(len as usize)
+                .map_err(|e| FileAccessError(e, "reading icon"))?;
+            return Ok(Some(GameImage::from_reader(&mut BufReader::new(&*data))?));
+        }
+        reader
+            .seek_relative(len as i64)
+            .map_err(|e| FileAccessError(e, "skipping section"))?;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::metadata::GameMetadata;
+    use crate::test_utils::TestDir;
+    use std::fs;
+
+    fn icon() -> GameImage {
+        let mut colors = [Color::new(0, 0, 0); 16];
+        colors[1] = Color::new(255, 0, 0);
+        let pixels = (0..ICON_SIZE as usize * ICON_SIZE as usize)
+            .map(|i| (i % 16) as u8)
+            .collect();
+        GameImage::new_indexed(ICON_SIZE, ICON_SIZE, Palette::new(None, colors), pixels).unwrap()
+    }
+
+    #[test]
+    fn read_write() {
+        let icon = icon();
+        assert!(icon.validate_icon().is_ok());
+        assert_eq!(icon.rgba_at(0, 0), [0, 0, 0, 0]);
+        assert_eq!(icon.rgba_at(1, 0), [255, 0, 0, 255]);
+        let bytes = icon.as_bytes().unwrap();
+        assert_eq!(bytes.len(), 7 + 48 + 512);
+        assert_eq!(
+            GameImage::from_reader(&mut BufReader::new(&*bytes)).unwrap(),
+            icon
+        );
+
+        let cover = GameImage::new_rgba(3, 1, vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]).unwrap();
+        assert!(cover.validate_cover().is_ok());
+        assert!(cover.validate_icon().is_err());
+        assert_eq!(cover.rgba_at(2, 0), [9, 10, 11, 12]);
+        let bytes = cover.as_bytes().unwrap();
+        assert_eq!(
+            GameImage::from_reader(&mut BufReader::new(&*bytes)).unwrap(),
+            cover
+        );
+
+        assert!(GameImage::new_rgba(2, 2, vec![0; 15]).is_err());
+        assert!(GameImage::new_indexed(1, 1, icon_palette(), vec![16]).is_err());
+    }
+
+    fn icon_palette() -> Palette {
+        Palette::new(None, [Color::new(1, 2, 3); 16])
+    }
+
+    #[test]
+    fn fast_path() {
+        let header = GameFileHeader::new(
+            String::from("com.example.game"),
+            1,
+            1,
+            1,
+            0,
+            String::from("Game"),
+            String::from("1.0"),
+            String::from("Author"),
+            1,
+            2,
+        );
+        let mut file = GameFile::new(
+            header,
+            [1; MAIN_CODE],
+            vec![[2; CODE_BANK]],
+            vec![[3; ATLAS_BANK]; 2],
+        );
+        file.metadata = Some(GameMetadata {
+            license: Some(String::from("MIT")),
+            ..GameMetadata::default()
+        });
+        let dir = TestDir::new("icon");
+        let path = dir.join("game.mkr");
+
+        file.write(&path).unwrap();
+        assert_eq!(GameFile::read_icon(&path).unwrap(), None);
+
+        file.icon = Some(icon());
+        file.cover = Some(GameImage::new_rgba(2, 1, vec![7; 8]).unwrap());
+        file.write(&path).unwrap();
+        assert_eq!(GameFile::read_icon(&path).unwrap(), Some(icon()));
+        let parsed = GameFile::read(&path).unwrap();
+        assert_eq!(parsed.icon, Some(icon()));
+        assert_eq!(parsed.cover, file.cover);
+
+        let mut bytes = fs::read(&path).unwrap();
+        let icon_start = bytes.len() - file.sections_as_bytes().unwrap().len()
+            + 5
+            + file.metadata.as_ref().unwrap().as_bytes().unwrap().len();
+        assert_eq!(bytes[icon_start], SectionId::Icon as u8);
+        bytes[icon_start + 1..icon_start + 5].copy_from_slice(&u32::MAX.to_be_bytes());
+        fs::write(&path, bytes).unwrap();
+        assert!(matches!(
+            GameFile::read_icon(&path),
+            Err(GameFileError::InvalidSection(3, _))
+        ));
+    }
+}
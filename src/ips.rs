@@ -0,0 +1,203 @@
+use crate::read_write_impl::Writeable;
+use crate::GameFileError::{InvalidPatch, PatchNotApplicable};
+use crate::{GameFile, GameFileError};
+
+const IPS_HEADER: &[u8; 5] = b"PATCH";
+const IPS_FOOTER: &[u8; 3] = b"EOF";
+///Offset that can't be used by a record as it would be read as the footer
+const EOF_OFFSET: usize = 0x454F46;
+const MAX_OFFSET: usize = 0xFFFFFF;
+const MAX_RECORD: usize = 0xFFFF;
+///Records with at least this many identical bytes are written as RLE
+const MIN_RLE: usize = 8;
+
+/// Create an IPS patch that converts `source` into `target`
+///
+/// If `target` is shorter than `source` the truncation extension is used
+pub fn create_ips(source: &[u8], target: &[u8]) -> Result<Vec<u8>, GameFileError> {
+    if target.len() > MAX_OFFSET + 1 {
+        return Err(PatchNotApplicable(String::from(
+            "target is too large for an IPS patch",
+        )));
+    }
+    let mut output = IPS_HEADER.to_vec();
+    let mut i = 0;
+    while i < target.len() {
+        if source.get(i) == Some(&target[i]) {
+            i += 1;
+            continue;
+        }
+        let mut pos = i;
+        while i < target.len() && source.get(i) != Some(&target[i]) {
+            i += 1;
+        }
+        while pos < i {
+            if pos == EOF_OFFSET {
+                pos -= 1;
+            }
+            let chunk = &target[pos..i.min(pos + MAX_RECORD)];
+            output.extend_from_slice(&(pos as u32).to_be_bytes()[1..]);
+            if chunk.len() >= MIN_RLE && chunk.iter().all(|b| *b == chunk[0]) {
+                output.extend_from_slice(&[0, 0]);
+                output.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+                output.push(chunk[0]);
+            } else {
+                output.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+                output.extend_from_slice(chunk);
+            }
+            pos += chunk.len();
+        }
+    }
+    output.extend_from_slice(IPS_FOOTER);
+    if target.len() < source.len() {
+        output.extend_from_slice(&(target.len() as u32).to_be_bytes()[1..]);
+    }
+    Ok(output)
+}
+
+/// Apply IPS `patch` to `source`
+pub fn apply_ips(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, GameFileError> {
+    if !patch.starts_with(IPS_HEADER) {
+        return Err(InvalidPatch(String::from("Not an IPS patch")));
+    }
+    let mut output = source.to_vec();
+    let mut pos = IPS_HEADER.len();
+    let take = |pos: &mut usize, len: usize| -> Result<&[u8], GameFileError> {
+        let bytes = patch
+            .get(*pos..*pos + len)
+            .ok_or_else(|| InvalidPatch(String::from("IPS patch is truncated")))?;
+        *pos += len;
+        Ok(bytes)
+    };
+    loop {
+        let offset = take(&mut pos, 3)?;
+        if offset == IPS_FOOTER {
+            break;
+        }
+        let offset = u32::from_be_bytes([0, offset[0], offset[1], offset[2]]) as usize;
+        let size = take(&mut pos, 2)?;
+        let size = u16::from_be_bytes([size[0], size[1]]) as usize;
+        let data = if size == 0 {
+            let rle = take(&mut pos, 3)?;
+            vec![rle[2]; u16::from_be_bytes([rle[0], rle[1]]) as usize]
+        } else {
+            take(&mut pos, size)?.to_vec()
+        };
+        let end = offset + data.len();
+        if output.len() < end {
+            output.resize(end, 0);
+        }
+        output[offset..end].copy_from_slice(&data);
+    }
+    match patch.len() - pos {
+        0 => {}
+        3 => {
+            let len = take(&mut pos, 3)?;
+            output.truncate(u32::from_be_bytes([0, len[0], len[1], len[2]]) as usize);
+        }
+        _ => {
+            return Err(InvalidPatch(String::from(
+                "Unexpected data after IPS footer",
+            )))
+        }
+    }
+    Ok(output)
+}
+
+impl GameFile {
+    /// Create IPS patch that converts this file into `target`
+    pub fn create_ips(&self, target: &GameFile) -> Result<Vec<u8>, GameFileError> {
+        create_ips(&self.as_bytes()?, &target.as_bytes()?)
+    }
+
+    /// Apply IPS patch to this file, the result is parsed and validated
+    pub fn apply_ips(&self, patch: &[u8]) -> Result<GameFile, GameFileError> {
+        GameFile::from_patched_bytes(&apply_ips(&self.as_bytes()?, patch)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constants::mem::{ATLAS_BANK, CODE_BANK, MAIN_CODE};
+    use crate::GameFileError::ValidationFailed;
+    use crate::GameFileHeader;
+
+    #[test]
+    fn known_patch() {
+        let source = [0, 1, 2, 3, 4, 5];
+        let patch = [
+            b'P', b'A', b'T', b'C', b'H', //header
+            0, 0, 1, 0, 2, 9, 9, //offset 1, 2 bytes
+            0, 0, 7, 0, 0, 0, 3, 8, //offset 7, rle 3 bytes of 8
+            b'E', b'O', b'F',
+        ];
+        assert_eq!(
+            apply_ips(&source, &patch).unwrap(),
+            vec![0, 9, 9, 3, 4, 5, 0, 8, 8, 8]
+        );
+        assert!(apply_ips(&source, &patch[..10]).is_err());
+        assert!(apply_ips(&source, b"PATCX").is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let source: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        let mut target = source.clone();
+        target[10] = 0;
+        target[50..70].fill(1);
+        target.extend_from_slice(&[5; 10]);
+        let patch = create_ips(&source, &target).unwrap();
+        assert_eq!(apply_ips(&source, &patch).unwrap(), target);
+
+        let shorter = &source[..150];
+        let patch = create_ips(&source, shorter).unwrap();
+        assert_eq!(apply_ips(&source, &patch).unwrap(), shorter);
+    }
+
+    #[test]
+    fn game_file() {
+        let header = GameFileHeader::new(
+            String::from("com.example.game"),
+            1,
+            1,
+            1,
+            0,
+            String::from("Game"),
+            String::from("1.0"),
+            String::from("Author"),
+            1,
+            1,
+        );
+        let source = GameFile::new(
+            header,
+            [1; MAIN_CODE],
+            vec![[2; CODE_BANK]],
+            vec![[3; ATLAS_BANK]],
+        );
+        let mut target = GameFile::new(
+            GameFileHeader {
+                build: 2,
+                ..source.header.clone()
+            },
+            source.main_code,
+            source.code_banks.clone(),
+            source.atlases.clone(),
+        );
+        target.main_code[100] = 0;
+        let patch = source.create_ips(&target).unwrap();
+        let patched = source.apply_ips(&patch).unwrap();
+        assert_eq!(patched.as_bytes().unwrap(), target.as_bytes().unwrap());
+
+        //patch that sets build to 0
+        let source_bytes = source.as_bytes().unwrap();
+        let mut invalid = source_bytes.clone();
+        invalid[7..11].copy_from_slice(&[0, 0, 0, 0]);
+        let patch = create_ips(&source_bytes, &invalid).unwrap();
+        assert!(matches!(source.apply_ips(&patch), Err(ValidationFailed(_))));
+
+        //patch that removes the atlas bank
+        let patch = create_ips(&source_bytes, &source_bytes[..source_bytes.len() - 10]).unwrap();
+        assert!(source.apply_ips(&patch).is_err());
+    }
+}
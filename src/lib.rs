@@ -1,14 +1,46 @@
 pub mod atlas_file;
-mod constants;
+pub mod atlas_optimizer;
+pub mod atlas_packer;
+pub mod bps;
+pub mod build;
+pub mod build_cache;
+pub mod build_info;
+mod checksum;
+pub mod color;
+pub mod compatibility;
+pub mod constants;
+pub mod extract;
 mod file_utils;
 pub mod game_file;
 pub mod game_header;
+pub mod game_id;
+pub mod game_version;
+pub mod icon;
+pub mod ips;
+pub mod localization;
 pub mod manifest;
+pub mod manifest_files;
+pub mod manifest_format;
+pub mod manifest_profile;
+pub mod metadata;
 pub mod palette;
+pub mod palette_format;
+pub mod palette_set;
+pub mod patch;
+mod png;
 pub mod read_write_impl;
+pub mod section;
+#[cfg(test)]
+mod test_utils;
 
+use crate::build_info::BuildInfo;
 use crate::constants::mem::*;
+use crate::icon::GameImage;
+use crate::localization::Localizations;
+use crate::metadata::GameMetadata;
+use crate::palette_set::PaletteSet;
 use crate::GameFileError::{FileFormatInvalid, InvalidFileVersion};
+use serde::Serialize;
 use std::fmt::Debug;
 use std::io;
 use thiserror::Error;
@@ -16,9 +48,14 @@ use thiserror::Error;
 const ID_HEADER: [u8; 2] = [0xFD, 0xA1];
 const MAIKOR_HEADER_LENGTH: usize = 16;
 const FILE_FORMAT_VER: u8 = 1;
+///Max length in bytes (UTF-8) of header strings, after trimming whitespace
 const MAX_STRING_LEN: usize = 255;
-const MIN_FILE_SIZE: u64 = MAIKOR_HEADER_LENGTH as u64 + MAIN_CODE as u64 + 3;
-const MAX_FILE_SIZE: u64 = ATLAS_BANK as u64 * 255 + CODE_BANK as u64 * 255 + MIN_FILE_SIZE;
+///Smallest possible valid game file (header with empty strings and main code)
+pub const MIN_FILE_SIZE: u64 = MAIKOR_HEADER_LENGTH as u64 + MAIN_CODE as u64 + 3;
+const MAX_SECTIONS_SIZE: u64 = 1024 * 1024;
+///Largest possible valid game file (all banks and sections used)
+pub const MAX_FILE_SIZE: u64 =
+    ATLAS_BANK as u64 * 255 + CODE_BANK as u64 * 255 + MIN_FILE_SIZE + MAX_SECTIONS_SIZE;
 
 #[derive(Error, Debug)]
 pub enum GameFileError {
@@ -40,19 +77,53 @@ pub enum GameFileError {
     InvalidMaikorFile(),
     #[error("Invalid atlas banks")]
     InvalidAtlasBanks(),
+    #[error("Too many atlas banks, max is 255 and needed {0}")]
+    TooManyAtlasBanks(usize),
     #[error("Header validation failed:\n{0}")]
     InvalidHeader(&'static str),
-    #[error("{0} field is too long, max is {1} and was {2}")]
+    #[error("{0} field is too long, max is {1} bytes and was {2}")]
     FieldTooLong(&'static str, usize, usize),
+    #[error("{0} field is not valid UTF-8")]
+    InvalidUtf8(&'static str),
     #[error("Invalid Atlas file: {0}")]
     InvalidAtlas(String),
+    #[error("Invalid code file: {0}")]
+    InvalidCode(String),
+    #[error("Game file validation failed:\n{0}")]
+    ValidationFailed(String),
     #[error("Error parsing manifest: {0}")]
     ManifestParsingError(String),
+    #[error("Manifest syntax error at line {1}, column {2}: {0}")]
+    ManifestSyntaxError(String, usize, usize),
+    #[error("Invalid manifest file list:\n{0}")]
+    FilePatternError(String),
     #[error("Invalid Palette file: {0}")]
     InvalidPalette(String),
+    #[error("Palette must have 16 colors but had {0}")]
+    PaletteColorCount(usize),
+    #[error("Invalid color: {0}")]
+    InvalidColor(String),
+    #[error("Invalid section {0}: {1}")]
+    InvalidSection(u8, String),
+    #[error("Invalid patch file: {0}")]
+    InvalidPatch(String),
+    #[error("Patch can't be applied: {0}")]
+    PatchNotApplicable(String),
+    #[error("Invalid game version: {0}")]
+    InvalidGameVersion(String),
+    #[error("Invalid game ID: {0}")]
+    InvalidGameId(String),
+    #[error("Invalid metadata: {0}")]
+    InvalidMetadata(String),
+    #[error("Invalid image: {0}")]
+    InvalidImage(String),
+    #[error("Invalid localization: {0}")]
+    InvalidLocalization(String),
+    #[error("Invalid build info: {0}")]
+    InvalidBuildInfo(String),
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
 pub struct GameFileHeader {
     ///Unique ID for app
     pub id: String,
@@ -83,4 +154,16 @@ pub struct GameFile {
     pub code_banks: Vec<[u8; CODE_BANK]>,
     ///Atlas bank data
     pub atlases: Vec<[u8; ATLAS_BANK]>,
+    ///Palettes bundled with game (optional section)
+    pub palettes: Option<PaletteSet>,
+    ///Store/launcher information (optional section)
+    pub metadata: Option<GameMetadata>,
+    ///Small icon for launchers, must be [icon::ICON_SIZE] square (optional section)
+    pub icon: Option<GameImage>,
+    ///Larger cover art or screenshot (optional section)
+    pub cover: Option<GameImage>,
+    ///Translated name, description and author (optional section)
+    pub localizations: Option<Localizations>,
+    ///Hashes of the files used to build the game (optional section)
+    pub build_info: Option<BuildInfo>,
 }
@@ -0,0 +1,397 @@
+use crate::file_utils::{read_header_string, ReaderExt};
+use crate::game_header::convert_string;
+use crate::read_write_impl::{Readable, Writeable};
+use crate::GameFileError::{FieldTooLong, FileAccessError, InvalidLocalization, InvalidUtf8};
+use crate::{GameFile, GameFileError, MAX_STRING_LEN};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+
+const MAX_TAG_LEN: usize = 35;
+const MAX_DESCRIPTION_LEN: usize = u16::MAX as usize;
+const HAS_NAME: u8 = 0b001;
+const HAS_DESCRIPTION: u8 = 0b010;
+const HAS_AUTHOR: u8 = 0b100;
+
+/// Translated strings for a single locale, missing values fall back to the header/metadata
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct LocalizedStrings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = 1, max = 255))]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(length(min = 1, max = 255))]
+    pub author: Option<String>,
+}
+
+/// Translated strings keyed by BCP-47 language tag, i.e. `en`, `pt-BR` or `zh-Hant`
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct Localizations(pub BTreeMap<String, LocalizedStrings>);
+
+impl Localizations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, tag: &str, strings: LocalizedStrings) {
+        self.0.insert(tag.to_string(), strings);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Find the best match for `locale`
+    ///
+    /// Tags are compared case insensitively, if there's no exact match subtags are removed from
+    /// the end (`zh-Hant-TW`, `zh-Hant`, `zh`) until one is found
+    pub fn lookup(&self, locale: &str) -> Vec<&LocalizedStrings> {
+        let mut candidates = vec![];
+        let mut tag = locale.trim();
+        loop {
+            if let Some((_, strings)) = self.0.iter().find(|(key, _)| key.eq_ignore_ascii_case(tag))
+            {
+                candidates.push(strings);
+            }
+            match tag.rsplit_once('-') {
+                Some((rest, _)) => tag = rest,
+                None => break,
+            }
+        }
+        candidates
+    }
+
+    /// Check tags are valid BCP-47 and strings fit in the file format
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if self.0.len() > u8::MAX as usize {
+            errors.push(format!(
+                "Too many localizations, max is {} and was {}",
+                u8::MAX,
+                self.0.len()
+            ));
+        }
+        let mut seen: Vec<String> = vec![];
+        for (tag, strings) in &self.0 {
+            if let Err(error) = validate_tag(tag) {
+                errors.push(error);
+            }
+            let lower = tag.to_ascii_lowercase();
+            if seen.contains(&lower) {
+                errors.push(format!("Language tag '{tag}' is used more than once"));
+            }
+            seen.push(lower);
+            if strings == &LocalizedStrings::default() {
+                errors.push(format!("Localization for '{tag}' is empty"));
+            }
+            for (field, value) in [("Name", &strings.name), ("Author", &strings.author)] {
+                if let Some(value) = value {
+                    if value.trim().is_empty() {
+                        errors.push(format!(
+                            "{field} for '{tag}' must have at least one character"
+                        ));
+                    } else if value.trim().len() > MAX_STRING_LEN {
+                        errors.push(format!(
+                            "{field} for '{tag}' is too long, max of {MAX_STRING_LEN} bytes"
+                        ));
+                    }
+                }
+            }
+            if let Some(description) = &strings.description {
+                if description.len() > MAX_DESCRIPTION_LEN {
+                    errors.push(format!(
+                        "Description for '{tag}' is too long, max of {MAX_DESCRIPTION_LEN} bytes"
+                    ));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Check `tag` is a well formed BCP-47 language tag
+///
+/// Only the structure is checked (language, then alphanumeric subtags of 1-8 characters), not
+/// that the language or region exist
+pub fn validate_tag(tag: &str) -> Result<(), String> {
+    let error = |reason: &str| Err(format!("Language tag '{tag}' {reason}"));
+    if tag.is_empty() || tag.len() > MAX_TAG_LEN {
+        return error(&format!("must be 1-{MAX_TAG_LEN} characters"));
+    }
+    let mut subtags = tag.split('-');
+    let language = subtags.next().unwrap_or_default();
+    if !(2..=8).contains(&language.len()) || language.len() == 4 {
+        return error("must start with a 2-3 or 5-8 letter language");
+    }
+    if !language.chars().all(|c| c.is_ascii_alphabetic()) {
+        return error("language must only contain letters");
+    }
+    for subtag in subtags {
+        if subtag.is_empty() || subtag.len() > 8 {
+            return error("has a subtag that isn't 1-8 characters");
+        }
+        if !subtag.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return error("must only contain letters, digits and -");
+        }
+    }
+    Ok(())
+}
+
+impl GameFile {
+    fn localized(&self, locale: &str) -> Vec<&LocalizedStrings> {
+        self.localizations
+            .as_ref()
+            .map(|localizations| localizations.lookup(locale))
+            .unwrap_or_default()
+    }
+
+    /// Name for `locale`, falls back to the header name
+    pub fn localized_name(&self, locale: &str) -> &str {
+        self.localized(locale)
+            .into_iter()
+            .find_map(|strings| strings.name.as_deref())
+            .unwrap_or(&self.header.name)
+    }
+
+    /// Author for `locale`, falls back to the header author
+    pub fn localized_author(&self, locale: &str) -> &str {
+        self.localized(locale)
+            .into_iter()
+            .find_map(|strings| strings.author.as_deref())
+            .unwrap_or(&self.header.author)
+    }
+
+    /// Description for `locale`, falls back to the metadata description
+    pub fn localized_description(&self, locale: &str) -> Option<&str> {
+        self.localized(locale)
+            .into_iter()
+            .find_map(|strings| strings.description.as_deref())
+            .or_else(|| {
+                self.metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.description.as_deref())
+            })
+    }
+}
+
+impl Writeable for Localizations {
+    fn as_bytes(&self) -> Result<Vec<u8>, GameFileError> {
+        if self.0.len() > u8::MAX as usize {
+            return Err(InvalidLocalization(format!(
+                "Too many localizations, max is {}",
+                u8::MAX
+            )));
+        }
+        let mut output = vec![self.0.len() as u8];
+        for (tag, strings) in &self.0 {
+            output.extend_from_slice(&convert_string("Language tag", tag)?);
+            let mut flags = 0;
+            if strings.name.is_some() {
+                flags |= HAS_NAME;
+            }
+            if strings.description.is_some() {
+                flags |= HAS_DESCRIPTION;
+            }
+            if strings.author.is_some() {
+                flags |= HAS_AUTHOR;
+            }
+            output.push(flags);
+            if let Some(name) = &strings.name {
+                output.extend_from_slice(&convert_string("Localized name", name)?);
+            }
+            if let Some(description) = &strings.description {
+                if description.len() > MAX_DESCRIPTION_LEN {
+                    return Err(FieldTooLong(
+                        "Localized description",
+                        MAX_DESCRIPTION_LEN,
+                        description.len(),
+                    ));
+                }
+                output.extend_from_slice(&(description.len() as u16).to_be_bytes());
+                output.extend_from_slice(description.as_bytes());
+            }
+            if let Some(author) = &strings.author {
+                output.extend_from_slice(&convert_string("Localized author", author)?);
+            }
+        }
+        Ok(output)
+    }
+}
+
+impl Readable for Localizations {
+    fn from_reader<R: ReaderExt>(reader: &mut R) -> Result<Self, GameFileError>
+    where
+        Self: Sized,
+    {
+        let count = reader
+            .read_u8()
+            .map_err(|e| FileAccessError(e, "reading localization count"))?;
+        let mut localizations = Localizations::new();
+        for _ in 0..count {
+            let tag = read_header_string(reader, "Language tag", "reading language tag")?;
+            if localizations.0.contains_key(&tag) {
+                return Err(InvalidLocalization(format!(
+                    "Duplicate language tag '{tag}'"
+                )));
+            }
+            let flags = reader
+                .read_u8()
+                .map_err(|e| FileAccessError(e, "reading localization flags"))?;
+            let mut strings = LocalizedStrings::default();
+            if flags & HAS_NAME != 0 {
+                strings.name = Some(read_header_string(
+                    reader,
+                    "Localized name",
+                    "reading localized name",
+                )?);
+            }
+            if flags & HAS_DESCRIPTION != 0 {
+                let len = reader
+                    .read_u16()
+                    .map_err(|e| FileAccessError(e, "reading localized description length"))?;
+                strings.description = Some(reader.read_string(len as usize).map_err(|e| {
+                    if e.kind() == io::ErrorKind::InvalidData {
+                        InvalidUtf8("Localized description")
+                    } else {
+                        FileAccessError(e, "reading localized description")
+                    }
+                })?);
+            }
+            if flags & HAS_AUTHOR != 0 {
+                strings.author = Some(read_header_string(
+                    reader,
+                    "Localized author",
+                    "reading localized author",
+                )?);
+            }
+            localizations.insert(&tag, strings);
+        }
+        Ok(localizations)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constants::mem::{ATLAS_BANK, MAIN_CODE};
+    use crate::GameFileHeader;
+    use std::io::BufReader;
+
+    fn localizations() -> Localizations {
+        let mut localizations = Localizations::new();
+        localizations.insert(
+            "ja",
+            LocalizedStrings {
+                name: Some(String::from("ゲーム")),
+                description: Some(String::from("説明")),
+                author: None,
+            },
+        );
+        localizations.insert(
+            "pt-BR",
+            LocalizedStrings {
+                name: Some(String::from("Jogo")),
+                ..LocalizedStrings::default()
+            },
+        );
+        localizations.insert(
+            "pt",
+            LocalizedStrings {
+                name: Some(String::from("Jogo PT")),
+                author: Some(String::from("Autor")),
+                ..LocalizedStrings::default()
+            },
+        );
+        localizations
+    }
+
+    #[test]
+    fn read_write() {
+        let localizations = localizations();
+        assert!(localizations.validate().is_ok());
+        let bytes = localizations.as_bytes().unwrap();
+        let parsed = Localizations::from_reader(&mut BufReader::new(&*bytes)).unwrap();
+        assert_eq!(parsed, localizations);
+
+        let mut single = Localizations::new();
+        single.insert("ja", LocalizedStrings::default());
+        let entry = &single.as_bytes().unwrap()[1..];
+        let duplicate = [&[2], entry, entry].concat();
+        assert!(matches!(
+            Localizations::from_reader(&mut BufReader::new(&*duplicate)),
+            Err(InvalidLocalization(_))
+        ));
+    }
+
+    #[test]
+    fn lookup() {
+        let header = GameFileHeader::new(
+            String::from("com.example.game"),
+            1,
+            1,
+            1,
+            0,
+            String::from("Game"),
+            String::from("1.0"),
+            String::from("Author"),
+            0,
+            1,
+        );
+        let mut file = GameFile::new(header, [0; MAIN_CODE], vec![], vec![[0; ATLAS_BANK]]);
+        assert_eq!(file.localized_name("ja"), "Game");
+        file.localizations = Some(localizations());
+
+        assert_eq!(file.localized_name("ja"), "ゲーム");
+        assert_eq!(file.localized_name("JA-jp"), "ゲーム");
+        assert_eq!(file.localized_description("ja"), Some("説明"));
+        assert_eq!(file.localized_author("ja"), "Author");
+        assert_eq!(file.localized_name("pt-BR"), "Jogo");
+        assert_eq!(file.localized_author("pt-BR"), "Autor");
+        assert_eq!(file.localized_name("pt-PT"), "Jogo PT");
+        assert_eq!(file.localized_name("fr"), "Game");
+        assert_eq!(file.localized_description("fr"), None);
+    }
+
+    #[test]
+    fn validate() {
+        for tag in ["en", "pt-BR", "zh-Hant-TW", "es-419", "de-CH-1996"] {
+            assert!(validate_tag(tag).is_ok(), "{tag}");
+        }
+        for tag in [
+            "",
+            "e",
+            "english1",
+            "en_GB",
+            "en-",
+            "en--GB",
+            "abcd",
+            "en-toolongsubtag",
+        ] {
+            assert!(validate_tag(tag).is_err(), "{tag}");
+        }
+
+        let mut localizations = localizations();
+        localizations.insert("PT-br", LocalizedStrings::default());
+        localizations.insert(
+            "fr",
+            LocalizedStrings {
+                name: Some(String::from(" ")),
+                ..LocalizedStrings::default()
+            },
+        );
+        assert_eq!(localizations.validate().unwrap_err().len(), 3);
+    }
+}
@@ -1,37 +1,493 @@
-use crate::read_write_impl::validate_file;
-use crate::GameFileError;
+use crate::atlas_file::ATLAS_SPRITE_SIZE;
+use crate::atlas_packer::plan_atlases;
+use crate::compatibility::PLATFORM_VERSION;
+use crate::constants::mem::{ATLAS_BANK, CODE_BANK, MAIN_CODE};
+use crate::game_id::GameId;
+use crate::localization::Localizations;
+use crate::manifest_format::ManifestFormat;
+use crate::manifest_profile::{BuildNumber, ManifestProfile};
+use crate::metadata::GameMetadata;
+use crate::read_write_impl::{get_file_size, validate_file};
+use crate::GameFileError::{InvalidAtlas, InvalidCode, ManifestParsingError, ManifestSyntaxError};
+use crate::{GameFileError, GameFileHeader};
+use schemars::{schema_for, JsonSchema};
 use serde::{Deserialize, Serialize};
+use serde_json::error::Category;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+/// Project file describing how to build a game file
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
 pub struct Manifest {
+    ///Unique game ID in reverse domain format, i.e. `com.example.game`
+    #[schemars(
+        length(min = 3, max = 255),
+        regex(pattern = r"^[A-Za-z][A-Za-z0-9_]*(\.[A-Za-z][A-Za-z0-9_]*)+$")
+    )]
     pub id: String,
+    ///Game name
+    #[schemars(length(min = 1, max = 255))]
     pub name: String,
+    ///Game author
+    #[schemars(length(min = 1, max = 255))]
     pub author: String,
+    ///Game version, semantic versions (`1.2.0`) can be compared by stores, can use variables
+    #[schemars(length(min = 1, max = 255))]
     pub version: String,
-    pub build: u32,
+    ///Build number, must be higher than any previously released build, can use variables
+    pub build: BuildNumber,
+    ///Main code file, max 9000 bytes
     pub main_code: String,
+    ///Minimum supported Maikor version
     pub min_maikor_version: u16,
+    ///Target Maikor version, if not set the version of `maikor-platform` used to build is used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compiled_for_maikor_version: Option<u16>,
+    ///Code bank files, max 4200 bytes each, can be glob patterns or directories
+    #[serde(default)]
+    #[schemars(length(max = 255))]
     pub code_files: Vec<String>,
+    ///Atlas files, packed into atlas banks, can be glob patterns or directories
+    #[schemars(length(min = 1, max = 255))]
     pub atlas_files: Vec<String>,
+    ///Number of RAM banks needed by game
+    #[serde(default)]
     pub ram_banks: u8,
+    ///Palette files to bundle with the game (mpal, gpl, pal, act or hex)
+    #[serde(default)]
+    #[schemars(length(max = 255))]
+    pub palette_files: Vec<String>,
+    ///Store/launcher information, written to the metadata section
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<GameMetadata>,
+    ///Icon image file (`.mimg`), must be 32x32
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon_file: Option<String>,
+    ///Cover image file (`.mimg`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_file: Option<String>,
+    ///Translated strings keyed by BCP-47 language tag
+    #[serde(default, skip_serializing_if = "Localizations::is_empty")]
+    pub localizations: Localizations,
+    ///Write a build info section with hashes of the manifest and input files, used to verify builds
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub build_info: bool,
+    ///Variants of the game (i.e. `debug`) that override fields, see [Manifest::resolve]
+    #[serde(
+        default,
+        rename = "profile",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub profiles: BTreeMap<String, ManifestProfile>,
+}
+
+/// Path of the shipped manifest JSON Schema, relative to the crate root
+pub const MANIFEST_SCHEMA_FILE: &str = "manifest.schema.json";
+
+/// JSON Schema for [Manifest], as shipped in `manifest.schema.json`
+pub fn manifest_schema() -> String {
+    let schema = schema_for!(Manifest);
+    let mut json = serde_json::to_string_pretty(&schema).expect("schema is valid JSON");
+    json.push('\n');
+    json
 }
 
 impl Manifest {
+    /// Read a manifest file, the format is detected from the file extension
+    ///
+    /// Files with an unknown extension are parsed as JSON
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Manifest, GameFileError> {
+        let path = path.as_ref();
+        Manifest::from_file_with_format(path, ManifestFormat::from_path(path).unwrap_or_default())
+    }
+
+    /// Read a manifest file in `format`, ignoring the file extension
+    pub fn from_file_with_format<P: AsRef<Path>>(
+        path: P,
+        format: ManifestFormat,
+    ) -> Result<Manifest, GameFileError> {
         let path = path.as_ref();
         validate_file(path, false)?;
         let text = fs::read_to_string(path)
             .map_err(|e| GameFileError::FileAccessError(e, "reading manifest"))?;
-        let manifest = serde_json::from_str(&text)
-            .map_err(|e| GameFileError::ManifestParsingError(e.to_string()))?;
-        Ok(manifest)
+        Manifest::parse(&text, format)
     }
 
+    /// Parse manifest JSON, syntax errors are reported as [ManifestSyntaxError] with the
+    /// line and column
     pub fn from_string(text: &str) -> Result<Manifest, GameFileError> {
-        let manifest = serde_json::from_str(text)
-            .map_err(|e| GameFileError::ManifestParsingError(e.to_string()))?;
-        Ok(manifest)
+        serde_json::from_str(text).map_err(convert_json_error)
+    }
+}
+
+fn convert_json_error(error: serde_json::Error) -> GameFileError {
+    let (line, column) = (error.line(), error.column());
+    let message = error.to_string();
+    let message = message
+        .strip_suffix(&format!(" at line {line} column {column}"))
+        .unwrap_or(&message)
+        .to_string();
+    match error.classify() {
+        Category::Syntax | Category::Eof => ManifestSyntaxError(message, line, column),
+        Category::Data | Category::Io => {
+            ManifestParsingError(format!("{message} (line {line}, column {column})"))
+        }
+    }
+}
+
+impl Manifest {
+    /// ID as it will be written to the game file, IDs are normalized to lowercase if valid
+    ///
+    /// Invalid IDs are left as is so they're reported by validation with any other errors
+    pub(crate) fn normalized_id(&self) -> String {
+        self.id
+            .parse::<GameId>()
+            .map(String::from)
+            .unwrap_or_else(|_| self.id.clone())
+    }
+
+    /// Target Maikor version written to the game file
+    ///
+    /// If `compiled_for_maikor_version` isn't set this is [PLATFORM_VERSION] (or
+    /// `min_maikor_version` if that is newer)
+    pub fn compiled_for_version(&self) -> u16 {
+        self.compiled_for_maikor_version
+            .unwrap_or_else(|| PLATFORM_VERSION.0.max(self.min_maikor_version))
+    }
+
+    /// Header for the game file built from this manifest
+    ///
+    /// File lists are expanded with [Manifest::expand_files] first. Code bank count is the number
+    /// of code files, atlas bank count is calculated from the atlas file sizes (relative to
+    /// `base_dir`) using the same layout as [pack_atlases]
+    pub fn to_header<P: AsRef<Path>>(&self, base_dir: P) -> Result<GameFileHeader, GameFileError> {
+        let base_dir = base_dir.as_ref();
+        let mut manifest = self.clone();
+        manifest.expand_files(base_dir)?;
+        if manifest.code_files.len() > MAX_COUNT {
+            return Err(InvalidCode(format!(
+                "Too many code files, max is {MAX_COUNT} and was {}",
+                manifest.code_files.len()
+            )));
+        }
+        let mut tile_counts = vec![];
+        for file in &manifest.atlas_files {
+            let path = base_dir.join(file);
+            validate_file(&path, false)?;
+            let size = get_file_size(&path) as usize;
+            if !size.is_multiple_of(ATLAS_SPRITE_SIZE) {
+                return Err(InvalidAtlas(format!(
+                    "{} is not a multiple of {ATLAS_SPRITE_SIZE} bytes, was {size}",
+                    path.display()
+                )));
+            }
+            tile_counts.push(size / ATLAS_SPRITE_SIZE);
+        }
+        let (_, atlas_bank_count) = plan_atlases(&tile_counts)?;
+        Ok(manifest.header(manifest.code_files.len() as u8, atlas_bank_count as u8))
+    }
+
+    pub(crate) fn header(&self, code_bank_count: u8, atlas_bank_count: u8) -> GameFileHeader {
+        GameFileHeader::new(
+            self.normalized_id(),
+            self.build.number().unwrap_or_default(),
+            self.compiled_for_version(),
+            self.min_maikor_version,
+            self.ram_banks,
+            self.name.clone(),
+            self.version.clone(),
+            self.author.clone(),
+            code_bank_count,
+            atlas_bank_count,
+        )
+    }
+
+    /// Check the manifest can be built
+    ///
+    /// Header fields are checked with the same rules as [GameFileHeader::validate], and every
+    /// referenced file must exist (relative to `base_dir`) and fit in its bank. Patterns and
+    /// directories in file lists are expanded first, see [Manifest::expand_files]
+    pub fn validate<P: AsRef<Path>>(&self, base_dir: P) -> Result<(), Vec<String>> {
+        let base_dir = base_dir.as_ref();
+        let mut expanded = self.clone();
+        let (_, mut errors) = expanded.expand_file_lists(base_dir);
+        expanded.check(base_dir, &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn check(&self, base_dir: &Path, errors: &mut Vec<String>) {
+        let unresolved = self.unresolved();
+        let mut header = self.header(0, self.atlas_files.len().min(MAX_COUNT) as u8);
+        if self.build.number().is_none() {
+            //already reported as unresolved
+            header.build = 1;
+        }
+        errors.extend(unresolved);
+        if let Err(header_errors) = header.validate() {
+            errors.extend(header_errors.lines().map(String::from));
+        }
+
+        for (field, count) in [
+            ("code_files", self.code_files.len()),
+            ("atlas_files", self.atlas_files.len()),
+            ("palette_files", self.palette_files.len()),
+        ] {
+            if count > MAX_COUNT {
+                errors.push(format!(
+                    "{field} has too many files, max is {MAX_COUNT} and was {count}"
+                ));
+            }
+        }
+
+        check_file(errors, base_dir, "main_code", &self.main_code, |size| {
+            max_size(size, MAIN_CODE)
+        });
+        for (i, file) in self.code_files.iter().enumerate() {
+            check_file(
+                errors,
+                base_dir,
+                &format!("code_files[{i}]"),
+                file,
+                |size| max_size(size, CODE_BANK),
+            );
+        }
+        for (i, file) in self.atlas_files.iter().enumerate() {
+            check_file(
+                errors,
+                base_dir,
+                &format!("atlas_files[{i}]"),
+                file,
+                |size| {
+                    if size == 0 || size % ATLAS_SPRITE_SIZE as u64 != 0 {
+                        Err(format!(
+                            "not a multiple of {ATLAS_SPRITE_SIZE} bytes, was {size}"
+                        ))
+                    } else {
+                        max_size(size, ATLAS_BANK)
+                    }
+                },
+            );
+        }
+        for (i, file) in self.palette_files.iter().enumerate() {
+            check_file(
+                errors,
+                base_dir,
+                &format!("palette_files[{i}]"),
+                file,
+                |_| Ok(()),
+            );
+        }
+        if let Some(file) = &self.icon_file {
+            check_file(errors, base_dir, "icon_file", file, |_| Ok(()));
+        }
+        if let Some(file) = &self.cover_file {
+            check_file(errors, base_dir, "cover_file", file, |_| Ok(()));
+        }
+
+        if let Some(metadata) = &self.metadata {
+            if let Err(metadata_errors) = metadata.validate() {
+                errors.extend(metadata_errors);
+            }
+        }
+        if let Err(localization_errors) = self.localizations.validate() {
+            errors.extend(localization_errors);
+        }
+    }
+}
+
+const MAX_COUNT: usize = u8::MAX as usize;
+
+fn max_size(size: u64, max: usize) -> Result<(), String> {
+    if size > max as u64 {
+        Err(format!("too large, max is {max} bytes and was {size}"))
+    } else {
+        Ok(())
+    }
+}
+
+fn check_file<F: Fn(u64) -> Result<(), String>>(
+    errors: &mut Vec<String>,
+    base_dir: &Path,
+    field: &str,
+    file: &str,
+    check_size: F,
+) {
+    let path = base_dir.join(file);
+    if !path.is_file() {
+        errors.push(format!("{field}: '{file}' doesn't exist or isn't a file"));
+    } else if let Err(error) = check_size(get_file_size(&path)) {
+        errors.push(format!("{field}: '{file}' is {error}"));
+    }
+}
+
+impl Manifest {
+    /// Replace a single field, used to change values (such as the build number) without editing
+    /// the manifest file
+    ///
+    /// Supported keys are `id`, `name`, `author`, `version`, `build`, `main_code`,
+    /// `min_maikor_version`, `compiled_for_maikor_version` and `ram_banks`
+    pub fn apply_override(&mut self, key: &str, value: &str) -> Result<(), GameFileError> {
+        fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, GameFileError> {
+            value
+                .parse()
+                .map_err(|_| ManifestParsingError(format!("Invalid value for {key}: {value}")))
+        }
+
+        match key {
+            "id" => self.id = value.to_string(),
+            "name" => self.name = value.to_string(),
+            "author" => self.author = value.to_string(),
+            "version" => self.version = value.to_string(),
+            "main_code" => self.main_code = value.to_string(),
+            "build" => self.build = BuildNumber::Number(parse(key, value)?),
+            "min_maikor_version" => self.min_maikor_version = parse(key, value)?,
+            "compiled_for_maikor_version" => {
+                self.compiled_for_maikor_version = Some(parse(key, value)?)
+            }
+            "ram_banks" => self.ram_banks = parse(key, value)?,
+            "build_info" => self.build_info = parse(key, value)?,
+            _ => return Err(ManifestParsingError(format!("Unknown override: {key}"))),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::TestDir;
+    use std::env;
+
+    #[test]
+    fn overrides() {
+        let mut manifest = Manifest::default();
+        manifest.apply_override("build", "42").unwrap();
+        manifest.apply_override("version", "1.2.0").unwrap();
+        assert_eq!(manifest.build, BuildNumber::Number(42));
+        assert_eq!(manifest.version, "1.2.0");
+        assert!(manifest.apply_override("build", "-1").is_err());
+        assert!(manifest.apply_override("colour", "red").is_err());
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let result =
+            Manifest::from_string("{\n  \"id\": \"com.example.game\",\n  \"name\" \"Game\"\n}");
+        match result {
+            Err(ManifestSyntaxError(message, line, column)) => {
+                assert_eq!((line, column), (3, 10));
+                assert_eq!(message, "expected `:`");
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+        assert!(matches!(
+            Manifest::from_string("{\"id\": 5}"),
+            Err(ManifestParsingError(_))
+        ));
+    }
+
+    #[test]
+    fn validate() {
+        let dir = TestDir::new("manifest");
+        fs::write(dir.join("main.bin"), [0; 10]).unwrap();
+        fs::write(dir.join("code.bin"), vec![0; CODE_BANK + 1]).unwrap();
+        fs::write(dir.join("atlas.bin"), [0; ATLAS_SPRITE_SIZE + 1]).unwrap();
+
+        let mut manifest = Manifest {
+            id: String::from("Com.Example.Game"),
+            name: String::from("Game"),
+            author: String::from("Author"),
+            version: String::from("1.0"),
+            build: 1.into(),
+            main_code: String::from("main.bin"),
+            min_maikor_version: 1,
+            atlas_files: vec![String::from("atlas.bin")],
+            code_files: vec![String::from("code.bin"), String::from("missing.bin")],
+            ..Manifest::default()
+        };
+        let errors = manifest.validate(&dir).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                format!("code_files[0]: 'code.bin' is too large, max is {CODE_BANK} bytes and was {}", CODE_BANK + 1),
+                String::from("code_files[1]: 'missing.bin' doesn't exist or isn't a file"),
+                format!("atlas_files[0]: 'atlas.bin' is not a multiple of {ATLAS_SPRITE_SIZE} bytes, was {}", ATLAS_SPRITE_SIZE + 1),
+            ]
+        );
+
+        fs::write(dir.join("code.bin"), [0; 10]).unwrap();
+        fs::write(dir.join("atlas.bin"), [0; ATLAS_SPRITE_SIZE]).unwrap();
+        manifest.code_files.pop();
+        assert_eq!(manifest.validate(&dir), Ok(()));
+
+        manifest.id = String::from("game");
+        manifest.build = 0.into();
+        manifest.code_files = vec![String::from("code.bin"); 256];
+        let errors = manifest.validate(&dir).unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0], "Build version must be at least 1");
+    }
+
+    #[test]
+    fn defaults_and_header() {
+        let manifest = Manifest::from_string(
+            r#"{"id": "com.example.game", "name": "Game", "author": "Author", "version": "1.0",
+            "build": 2, "main_code": "main.bin", "min_maikor_version": 0,
+            "atlas_files": ["a.bin", "b.bin", "c.bin"]}"#,
+        )
+        .unwrap();
+        assert!(manifest.code_files.is_empty());
+        assert_eq!(manifest.ram_banks, 0);
+        assert_eq!(manifest.compiled_for_version(), PLATFORM_VERSION.0);
+
+        let dir = TestDir::new("manifest-header");
+        fs::write(dir.join("a.bin"), vec![0; ATLAS_SPRITE_SIZE * 100]).unwrap();
+        fs::write(dir.join("b.bin"), vec![0; ATLAS_SPRITE_SIZE * 50]).unwrap();
+        fs::write(dir.join("c.bin"), vec![0; ATLAS_SPRITE_SIZE * 25]).unwrap();
+        let header = manifest.to_header(&dir).unwrap();
+        assert_eq!(header.id, "com.example.game");
+        assert_eq!(header.code_bank_count, 0);
+        assert_eq!(header.atlas_bank_count, 2);
+        assert_eq!(header.compiled_for_maikor_version, PLATFORM_VERSION.0);
+
+        fs::create_dir_all(dir.join("code")).unwrap();
+        fs::write(dir.join("code/1.bin"), [0]).unwrap();
+        fs::write(dir.join("code/2.bin"), [0]).unwrap();
+        let expanded = Manifest {
+            code_files: vec![String::from("code/")],
+            atlas_files: vec![String::from("*.bin")],
+            ..manifest.clone()
+        };
+        let header = expanded.to_header(&dir).unwrap();
+        assert_eq!(header.code_bank_count, 2);
+        assert_eq!(header.atlas_bank_count, 2);
+
+        fs::write(dir.join("c.bin"), [0; ATLAS_SPRITE_SIZE + 1]).unwrap();
+        assert!(matches!(manifest.to_header(&dir), Err(InvalidAtlas(_))));
+
+        let manifest = Manifest {
+            min_maikor_version: 0x0203,
+            ..Manifest::default()
+        };
+        assert_eq!(manifest.compiled_for_version(), 0x0203);
+    }
+
+    #[test]
+    fn schema_is_up_to_date() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(MANIFEST_SCHEMA_FILE);
+        let schema = manifest_schema();
+        if env::var_os("MAIKOR_UPDATE_SCHEMA").is_some() {
+            fs::write(&path, &schema).unwrap();
+        }
+        let shipped = fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            shipped == schema,
+            "{MANIFEST_SCHEMA_FILE} is out of date, run tests with MAIKOR_UPDATE_SCHEMA=1 to update it"
+        );
     }
 }
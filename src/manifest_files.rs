@@ -0,0 +1,252 @@
+use crate::manifest::Manifest;
+use crate::GameFileError;
+use crate::GameFileError::FilePatternError;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+
+const GLOB_CHARS: &[char] = &['*', '?', '['];
+
+/// A glob pattern or directory in a manifest file list and the files it was replaced with
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct FileExpansion {
+    ///Field and index of the entry, i.e. `atlas_files[2]`
+    pub field: String,
+    ///Pattern or directory as written in the manifest
+    pub entry: String,
+    ///Matching files relative to the manifest dir, in natural order
+    pub files: Vec<String>,
+}
+
+impl Manifest {
+    /// Replace glob patterns (`atlases/*.bin`) and directories in `code_files` and `atlas_files`
+    /// with the files they contain (relative to `base_dir`)
+    ///
+    /// Matches are sorted naturally (`2.bin` before `10.bin`) so bank order is stable. Entries
+    /// that are existing files are left as is, returns the entries that were expanded
+    pub fn expand_files<P: AsRef<Path>>(
+        &mut self,
+        base_dir: P,
+    ) -> Result<Vec<FileExpansion>, GameFileError> {
+        let (expansions, errors) = self.expand_file_lists(base_dir.as_ref());
+        if errors.is_empty() {
+            Ok(expansions)
+        } else {
+            Err(FilePatternError(errors.join("\n")))
+        }
+    }
+
+    /// Expand file lists, entries that don't match anything are removed and reported as errors
+    pub(crate) fn expand_file_lists(
+        &mut self,
+        base_dir: &Path,
+    ) -> (Vec<FileExpansion>, Vec<String>) {
+        let mut expansions = vec![];
+        let mut errors = vec![];
+        for (field, files) in [
+            ("code_files", &mut self.code_files),
+            ("atlas_files", &mut self.atlas_files),
+        ] {
+            let mut expanded = vec![];
+            for (i, entry) in files.iter().enumerate() {
+                let field = format!("{field}[{i}]");
+                match expand_entry(base_dir, entry) {
+                    Ok(None) => expanded.push(entry.clone()),
+                    Ok(Some(matches)) if matches.is_empty() => {
+                        errors.push(format!("{field}: '{entry}' didn't match any files"))
+                    }
+                    Ok(Some(matches)) => {
+                        expanded.extend(matches.iter().cloned());
+                        expansions.push(FileExpansion {
+                            field,
+                            entry: entry.clone(),
+                            files: matches,
+                        });
+                    }
+                    Err(error) => errors.push(format!("{field}: '{entry}' {error}")),
+                }
+            }
+            *files = expanded;
+        }
+        (expansions, errors)
+    }
+}
+
+/// Files matching `entry` if it's a directory or pattern, `None` if it's a plain file
+fn expand_entry(base_dir: &Path, entry: &str) -> Result<Option<Vec<String>>, String> {
+    let path = base_dir.join(entry);
+    let mut files = if path.is_dir() {
+        let dir = fs::read_dir(&path).map_err(|e| format!("couldn't be read: {e}"))?;
+        let mut files = vec![];
+        for item in dir {
+            let item = item.map_err(|e| format!("couldn't be read: {e}"))?;
+            let name = item.file_name().to_string_lossy().to_string();
+            if !name.starts_with('.') && item.path().is_file() {
+                files.push(format!("{}/{}", entry.trim_end_matches('/'), name));
+            }
+        }
+        files
+    } else if !path.is_file() && entry.contains(GLOB_CHARS) {
+        //like `base_dir.join`, absolute entries are used as is
+        let absolute = Path::new(entry).is_absolute();
+        let pattern = if base_dir.as_os_str().is_empty() || absolute {
+            entry.to_string()
+        } else {
+            format!(
+                "{}/{}",
+                glob::Pattern::escape(&base_dir.to_string_lossy()),
+                entry
+            )
+        };
+        let paths = glob::glob(&pattern).map_err(|e| format!("is not a valid pattern: {e}"))?;
+        let mut files = vec![];
+        for path in paths {
+            let path = path.map_err(|e| format!("couldn't be read: {e}"))?;
+            if path.is_file() {
+                let relative = if absolute {
+                    &path
+                } else {
+                    path.strip_prefix(base_dir).unwrap_or(&path)
+                };
+                files.push(relative.to_string_lossy().replace('\\', "/"));
+            }
+        }
+        files
+    } else {
+        return Ok(None);
+    };
+    files.sort_by(|a, b| natural_cmp(a, b));
+    Ok(Some(files))
+}
+
+/// Compare strings treating runs of digits as numbers, so `atlas_2` is before `atlas_10`
+pub fn natural_cmp(lhs: &str, rhs: &str) -> Ordering {
+    let mut lhs = lhs.chars().peekable();
+    let mut rhs = rhs.chars().peekable();
+    loop {
+        match (lhs.peek().copied(), rhs.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) if a.is_ascii_digit() && b.is_ascii_digit() => {
+                let a = take_number(&mut lhs);
+                let b = take_number(&mut rhs);
+                let a_trimmed = a.trim_start_matches('0');
+                let b_trimmed = b.trim_start_matches('0');
+                let ordering = a_trimmed
+                    .len()
+                    .cmp(&b_trimmed.len())
+                    .then_with(|| a_trimmed.cmp(b_trimmed))
+                    .then_with(|| a.len().cmp(&b.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(a), Some(b)) => {
+                if a != b {
+                    return a.cmp(&b);
+                }
+                lhs.next();
+                rhs.next();
+            }
+        }
+    }
+}
+
+fn take_number<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        number.push(c);
+    }
+    number
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::TestDir;
+
+    #[test]
+    fn natural_order() {
+        let mut names = vec![
+            "atlas_10.bin",
+            "atlas_2.bin",
+            "atlas_1.bin",
+            "atlas_02.bin",
+            "b",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec![
+                "atlas_1.bin",
+                "atlas_2.bin",
+                "atlas_02.bin",
+                "atlas_10.bin",
+                "b"
+            ]
+        );
+    }
+
+    #[test]
+    fn expand() {
+        let dir = TestDir::new("expand");
+        fs::create_dir_all(dir.join("atlases")).unwrap();
+        fs::create_dir_all(dir.join("code")).unwrap();
+        for name in [
+            "atlases/a10.bin",
+            "atlases/a9.bin",
+            "atlases/a.png",
+            "code/1.bin",
+        ] {
+            fs::write(dir.join(name), [0]).unwrap();
+        }
+        fs::write(dir.join("code/.hidden"), [0]).unwrap();
+        fs::write(dir.join("main.bin"), [0]).unwrap();
+
+        let mut manifest = Manifest {
+            code_files: vec![String::from("main.bin"), String::from("code/")],
+            atlas_files: vec![String::from("atlases/*.bin")],
+            ..Manifest::default()
+        };
+        let expansions = manifest.expand_files(&dir).unwrap();
+        assert_eq!(manifest.code_files, vec!["main.bin", "code/1.bin"]);
+        assert_eq!(
+            manifest.atlas_files,
+            vec!["atlases/a9.bin", "atlases/a10.bin"]
+        );
+        assert_eq!(expansions.len(), 2);
+        assert_eq!(expansions[0].field, "code_files[1]");
+        assert_eq!(expansions[1].entry, "atlases/*.bin");
+
+        assert!(manifest.expand_files(&dir).unwrap().is_empty());
+
+        let pattern = format!(
+            "{}/atlases/*.bin",
+            glob::Pattern::escape(&dir.to_string_lossy())
+        );
+        manifest.atlas_files = vec![pattern];
+        manifest.expand_files(dir.join("code")).unwrap();
+        assert_eq!(
+            manifest.atlas_files,
+            vec![
+                dir.join("atlases/a9.bin")
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+                dir.join("atlases/a10.bin")
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            ]
+        );
+
+        manifest.atlas_files = vec![String::from("atlases/*.gif")];
+        match manifest.expand_files(&dir) {
+            Err(FilePatternError(message)) => assert_eq!(
+                message,
+                "atlas_files[0]: 'atlases/*.gif' didn't match any files"
+            ),
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+}
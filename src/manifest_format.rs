@@ -0,0 +1,317 @@
+use crate::extract::MANIFEST_FILE;
+use crate::manifest::Manifest;
+use crate::GameFileError;
+use crate::GameFileError::{FileAccessError, ManifestParsingError, ManifestSyntaxError};
+use ron::extensions::Extensions;
+use ron::ser::PrettyConfig;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Conventional name for TOML manifests
+pub const TOML_MANIFEST_FILE: &str = "maikor.toml";
+
+/// Supported manifest file formats
+///
+/// All formats describe the same [Manifest] fields and are validated identically
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+pub enum ManifestFormat {
+    ///JSON (`.json`)
+    #[default]
+    Json,
+    ///TOML (`.toml`), i.e. `maikor.toml`
+    Toml,
+    ///Rusty Object Notation (`.ron`), `Option` fields don't need to be wrapped in `Some`
+    Ron,
+}
+
+impl FromStr for ManifestFormat {
+    type Err = GameFileError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        ManifestFormat::from_extension(&text.to_lowercase()).ok_or_else(|| {
+            ManifestParsingError(format!(
+                "Unknown manifest format '{text}', expected json, toml or ron"
+            ))
+        })
+    }
+}
+
+impl ManifestFormat {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<ManifestFormat> {
+        let ext = path.as_ref().extension()?.to_string_lossy().to_lowercase();
+        ManifestFormat::from_extension(&ext)
+    }
+
+    pub fn from_extension(ext: &str) -> Option<ManifestFormat> {
+        match ext {
+            "json" => Some(ManifestFormat::Json),
+            "toml" => Some(ManifestFormat::Toml),
+            "ron" => Some(ManifestFormat::Ron),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ManifestFormat::Json => "json",
+            ManifestFormat::Toml => "toml",
+            ManifestFormat::Ron => "ron",
+        }
+    }
+
+    /// File name used when creating a project, i.e. `manifest.json` or `maikor.toml`
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ManifestFormat::Json => MANIFEST_FILE,
+            ManifestFormat::Toml => TOML_MANIFEST_FILE,
+            ManifestFormat::Ron => "manifest.ron",
+        }
+    }
+}
+
+impl Manifest {
+    /// Parse a manifest from `text` in `format`, syntax errors are reported as
+    /// [ManifestSyntaxError] with the line and column, invalid or missing fields as
+    /// [ManifestParsingError] (the same as JSON)
+    pub fn parse(text: &str, format: ManifestFormat) -> Result<Manifest, GameFileError> {
+        match format {
+            ManifestFormat::Json => Manifest::from_string(text),
+            ManifestFormat::Toml => {
+                //parse without the manifest types first so syntax errors can be told apart
+                text.parse::<toml::Table>()
+                    .map_err(|e| convert_toml_error(text, e, true))?;
+                toml::from_str(text).map_err(|e| convert_toml_error(text, e, false))
+            }
+            ManifestFormat::Ron => {
+                ron_options()
+                    .from_str::<ron::Value>(text)
+                    .map_err(|e| convert_ron_error(e, true))?;
+                ron_options()
+                    .from_str(text)
+                    .map_err(|e| convert_ron_error(e, false))
+            }
+        }
+    }
+
+    /// Convert this manifest to `format`
+    pub fn to_text(&self, format: ManifestFormat) -> Result<String, GameFileError> {
+        let mut text = match format {
+            ManifestFormat::Json => serde_json::to_string_pretty(self).map_err(|e| e.to_string()),
+            ManifestFormat::Toml => toml::to_string_pretty(self).map_err(|e| e.to_string()),
+            ManifestFormat::Ron => ron_options()
+                .to_string_pretty(self, PrettyConfig::new())
+                .map_err(|e| e.to_string()),
+        }
+        .map_err(ManifestParsingError)?;
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        Ok(text)
+    }
+
+    /// Write this manifest to `path`, the format is detected from the file extension
+    ///
+    /// Unknown extensions are written as JSON
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<(), GameFileError> {
+        let path = path.as_ref();
+        let format = ManifestFormat::from_path(path).unwrap_or_default();
+        fs::write(path, self.to_text(format)?).map_err(|e| FileAccessError(e, "writing manifest"))
+    }
+}
+
+fn ron_options() -> ron::Options {
+    ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME)
+}
+
+fn convert_toml_error(text: &str, error: toml::de::Error, syntax: bool) -> GameFileError {
+    let message = error.message().trim().to_string();
+    match error.span() {
+        Some(span) => {
+            let before = &text[..span.start.min(text.len())];
+            let line = before.matches('\n').count() + 1;
+            let column = before
+                .rsplit('\n')
+                .next()
+                .map(|line| line.chars().count() + 1)
+                .unwrap_or(1);
+            position_error(message, line, column, syntax)
+        }
+        None => ManifestParsingError(message),
+    }
+}
+
+fn convert_ron_error(error: ron::error::SpannedError, syntax: bool) -> GameFileError {
+    let position = error.span.start;
+    position_error(error.code.to_string(), position.line, position.col, syntax)
+}
+
+/// Classify errors like JSON, other errors keep the position in the message
+fn position_error(message: String, line: usize, column: usize, syntax: bool) -> GameFileError {
+    if syntax {
+        ManifestSyntaxError(message, line, column)
+    } else {
+        ManifestParsingError(format!("{message} (line {line}, column {column})"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::localization::LocalizedStrings;
+    use crate::metadata::GameMetadata;
+
+    fn manifest() -> Manifest {
+        let mut manifest = Manifest {
+            id: String::from("com.example.game"),
+            name: String::from("Game"),
+            author: String::from("Author"),
+            version: String::from("1.0.0"),
+            build: 3.into(),
+            main_code: String::from("main.bin"),
+            min_maikor_version: 1,
+            compiled_for_maikor_version: Some(2),
+            code_files: vec![String::from("code_0.bin")],
+            atlas_files: vec![String::from("atlas_0.bin")],
+            ram_banks: 2,
+            palette_files: vec![String::from("palettes/main.mpal")],
+            metadata: Some(GameMetadata {
+                description: Some(String::from("A game")),
+                tags: vec![String::from("puzzle")],
+                ..GameMetadata::default()
+            }),
+            icon_file: Some(String::from("icon.mimg")),
+            ..Manifest::default()
+        };
+        manifest.localizations.0.insert(
+            String::from("fr"),
+            LocalizedStrings {
+                name: Some(String::from("Jeu")),
+                ..LocalizedStrings::default()
+            },
+        );
+        manifest
+    }
+
+    #[test]
+    fn round_trip() {
+        let manifest = manifest();
+        for format in [
+            ManifestFormat::Json,
+            ManifestFormat::Toml,
+            ManifestFormat::Ron,
+        ] {
+            let text = manifest.to_text(format).unwrap();
+            let parsed = Manifest::parse(&text, format).unwrap();
+            assert_eq!(
+                serde_json::to_value(&parsed).unwrap(),
+                serde_json::to_value(&manifest).unwrap(),
+                "{format:?}\n{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn detect_format() {
+        assert_eq!(
+            ManifestFormat::from_path(TOML_MANIFEST_FILE),
+            Some(ManifestFormat::Toml)
+        );
+        assert_eq!(
+            ManifestFormat::from_path("game/Manifest.JSON"),
+            Some(ManifestFormat::Json)
+        );
+        assert_eq!(
+            ManifestFormat::from_path("manifest.ron"),
+            Some(ManifestFormat::Ron)
+        );
+        assert_eq!(ManifestFormat::from_path("manifest"), None);
+        assert_eq!(
+            "TOML".parse::<ManifestFormat>().ok(),
+            Some(ManifestFormat::Toml)
+        );
+        assert!("yaml".parse::<ManifestFormat>().is_err());
+    }
+
+    #[test]
+    fn parse_toml_and_ron() {
+        let toml = r#"
+# Comments are allowed
+id = "com.example.game"
+name = "Game"
+author = "Author"
+version = "1.0"
+build = 1
+main_code = "main.bin"
+min_maikor_version = 1
+code_files = []
+atlas_files = ["atlas.bin"]
+ram_banks = 0
+icon_file = "icon.mimg"
+"#;
+        let manifest = Manifest::parse(toml, ManifestFormat::Toml).unwrap();
+        assert_eq!(manifest.atlas_files, vec!["atlas.bin"]);
+        assert_eq!(manifest.icon_file.as_deref(), Some("icon.mimg"));
+
+        let ron = r#"(
+    // Comments are allowed
+    id: "com.example.game",
+    name: "Game",
+    author: "Author",
+    version: "1.0",
+    build: 1,
+    main_code: "main.bin",
+    min_maikor_version: 1,
+    code_files: [],
+    atlas_files: ["atlas.bin"],
+    ram_banks: 0,
+    icon_file: "icon.mimg",
+)"#;
+        let manifest = Manifest::parse(ron, ManifestFormat::Ron).unwrap();
+        assert_eq!(manifest.atlas_files, vec!["atlas.bin"]);
+        assert_eq!(manifest.icon_file.as_deref(), Some("icon.mimg"));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let result = Manifest::parse(
+            "id = \"com.example.game\"\nname \"Game\"\n",
+            ManifestFormat::Toml,
+        );
+        assert!(
+            matches!(result, Err(ManifestSyntaxError(_, 2, 6))),
+            "{result:?}"
+        );
+        let result = Manifest::parse(
+            "(\n  id: \"com.example.game\",\n  name \"Game\",\n)",
+            ManifestFormat::Ron,
+        );
+        assert!(
+            matches!(result, Err(ManifestSyntaxError(_, 3, _))),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn data_errors() {
+        for (text, format) in [
+            (
+                "id = \"com.example.game\"\nname = 5\n",
+                ManifestFormat::Toml,
+            ),
+            ("id = \"com.example.game\"\n", ManifestFormat::Toml),
+            (
+                "(\n  id: \"com.example.game\",\n  name: 5,\n)",
+                ManifestFormat::Ron,
+            ),
+            ("(\n  id: \"com.example.game\",\n)", ManifestFormat::Ron),
+            ("{\"id\": 5}", ManifestFormat::Json),
+        ] {
+            let result = Manifest::parse(text, format);
+            assert!(
+                matches!(result, Err(ManifestParsingError(_))),
+                "{format:?} {result:?}"
+            );
+        }
+    }
+}
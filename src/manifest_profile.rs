@@ -0,0 +1,331 @@
+use crate::manifest::Manifest;
+use crate::GameFileError;
+use crate::GameFileError::ManifestParsingError;
+use schemars::JsonSchema;
+use serde::de::{self, Unexpected, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::env;
+use std::fmt::{Display, Formatter};
+
+/// Build number in a manifest, either a number or text containing variables (`"${BUILD_NUMBER}"`)
+/// that is resolved by [Manifest::resolve]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum BuildNumber {
+    Number(#[schemars(range(min = 1))] u32),
+    Template(String),
+}
+
+impl BuildNumber {
+    /// Build number, `None` if this hasn't been resolved
+    pub fn number(&self) -> Option<u32> {
+        match self {
+            BuildNumber::Number(number) => Some(*number),
+            BuildNumber::Template(_) => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BuildNumber {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BuildVisitor;
+
+        impl Visitor<'_> for BuildVisitor {
+            type Value = BuildNumber;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                write!(f, "a build number or text with variables")
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+                u32::try_from(value)
+                    .map(BuildNumber::Number)
+                    .map_err(|_| E::invalid_value(Unexpected::Unsigned(value), &self))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+                u32::try_from(value)
+                    .map(BuildNumber::Number)
+                    .map_err(|_| E::invalid_value(Unexpected::Signed(value), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(BuildNumber::Template(value.to_string()))
+            }
+        }
+
+        deserializer.deserialize_any(BuildVisitor)
+    }
+}
+
+impl Default for BuildNumber {
+    fn default() -> Self {
+        BuildNumber::Number(0)
+    }
+}
+
+impl From<u32> for BuildNumber {
+    fn from(number: u32) -> Self {
+        BuildNumber::Number(number)
+    }
+}
+
+impl Display for BuildNumber {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildNumber::Number(number) => write!(f, "{number}"),
+            BuildNumber::Template(text) => write!(f, "{text}"),
+        }
+    }
+}
+
+/// Overrides applied to a [Manifest] when building a variant of the game, i.e. `[profile.debug]`
+///
+/// Set fields replace the manifest value, `extra_code_files` and `extra_atlas_files` are added
+/// after the manifest (or overridden) file lists
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ManifestProfile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<BuildNumber>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub main_code: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_maikor_version: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compiled_for_maikor_version: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_files: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_code_files: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub atlas_files: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_atlas_files: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ram_banks: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette_files: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_info: Option<bool>,
+}
+
+impl ManifestProfile {
+    fn apply(&self, manifest: &mut Manifest) {
+        fn set<T: Clone>(target: &mut T, value: &Option<T>) {
+            if let Some(value) = value {
+                *target = value.clone();
+            }
+        }
+
+        set(&mut manifest.id, &self.id);
+        set(&mut manifest.name, &self.name);
+        set(&mut manifest.author, &self.author);
+        set(&mut manifest.version, &self.version);
+        set(&mut manifest.build, &self.build);
+        set(&mut manifest.main_code, &self.main_code);
+        set(&mut manifest.min_maikor_version, &self.min_maikor_version);
+        if self.compiled_for_maikor_version.is_some() {
+            manifest.compiled_for_maikor_version = self.compiled_for_maikor_version;
+        }
+        set(&mut manifest.code_files, &self.code_files);
+        manifest
+            .code_files
+            .extend(self.extra_code_files.iter().cloned());
+        set(&mut manifest.atlas_files, &self.atlas_files);
+        manifest
+            .atlas_files
+            .extend(self.extra_atlas_files.iter().cloned());
+        set(&mut manifest.ram_banks, &self.ram_banks);
+        set(&mut manifest.palette_files, &self.palette_files);
+        if self.icon_file.is_some() {
+            manifest.icon_file = self.icon_file.clone();
+        }
+        if self.cover_file.is_some() {
+            manifest.cover_file = self.cover_file.clone();
+        }
+        set(&mut manifest.build_info, &self.build_info);
+    }
+}
+
+impl Manifest {
+    /// Create a concrete manifest by applying `profile` (if set) and replacing variables in
+    /// `version` and `build` using `vars`
+    ///
+    /// Variables are written as `${NAME}` or `${NAME:-default}`, use `$$` for a literal `$`.
+    /// The returned manifest has no profiles
+    pub fn resolve<F: Fn(&str) -> Option<String>>(
+        &self,
+        profile: Option<&str>,
+        vars: F,
+    ) -> Result<Manifest, GameFileError> {
+        let mut manifest = self.clone();
+        manifest.profiles.clear();
+        if let Some(name) = profile {
+            let profile = self.profiles.get(name).ok_or_else(|| {
+                let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                ManifestParsingError(format!(
+                    "Unknown profile '{name}', available: {}",
+                    if names.is_empty() {
+                        String::from("none")
+                    } else {
+                        names.join(", ")
+                    }
+                ))
+            })?;
+            profile.apply(&mut manifest);
+        }
+        manifest.version = substitute("version", &manifest.version, &vars)?;
+        if let BuildNumber::Template(text) = &manifest.build {
+            let value = substitute("build", text, &vars)?;
+            let number = value.trim().parse().map_err(|_| {
+                ManifestParsingError(format!("build '{text}' is not a number, was '{value}'"))
+            })?;
+            manifest.build = BuildNumber::Number(number);
+        }
+        Ok(manifest)
+    }
+
+    /// [Manifest::resolve] using environment variables
+    pub fn resolve_env(&self, profile: Option<&str>) -> Result<Manifest, GameFileError> {
+        self.resolve(profile, |name| env::var(name).ok())
+    }
+
+    /// Describes any fields that still need [Manifest::resolve]
+    pub(crate) fn unresolved(&self) -> Vec<String> {
+        let mut errors = vec![];
+        if let BuildNumber::Template(text) = &self.build {
+            errors.push(format!("build '{text}' has unresolved variables"));
+        }
+        if self.version.contains("${") {
+            errors.push(format!(
+                "version '{}' has unresolved variables",
+                self.version
+            ));
+        }
+        errors
+    }
+}
+
+/// Replace `${NAME}` and `${NAME:-default}` in `text`
+fn substitute<F: Fn(&str) -> Option<String>>(
+    field: &str,
+    text: &str,
+    vars: &F,
+) -> Result<String, GameFileError> {
+    let mut output = String::new();
+    let mut rest = text;
+    while let Some(idx) = rest.find('$') {
+        output.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            output.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after.find('}').ok_or_else(|| {
+                ManifestParsingError(format!("{field} '{text}' has unclosed variable"))
+            })?;
+            let (name, default) = match after[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&after[..end], None),
+            };
+            let value = vars(name)
+                .or_else(|| default.map(String::from))
+                .ok_or_else(|| {
+                    ManifestParsingError(format!("{field} uses variable {name} which is not set"))
+                })?;
+            output.push_str(&value);
+            rest = &after[end + 1..];
+        } else {
+            output.push('$');
+            rest = &rest[1..];
+        }
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::manifest_format::ManifestFormat;
+
+    fn vars(name: &str) -> Option<String> {
+        match name {
+            "BUILD_NUMBER" => Some(String::from("42")),
+            "GIT_SHA" => Some(String::from("abc123")),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn substitution() {
+        assert_eq!(
+            substitute("version", "1.0.0+${GIT_SHA}", &vars).unwrap(),
+            "1.0.0+abc123"
+        );
+        assert_eq!(
+            substitute("version", "${CHANNEL:-beta}-$$1", &vars).unwrap(),
+            "beta-$1"
+        );
+        assert_eq!(substitute("version", "1.0$", &vars).unwrap(), "1.0$");
+        assert!(substitute("version", "${CHANNEL}", &vars).is_err());
+        assert!(substitute("version", "${GIT_SHA", &vars).is_err());
+    }
+
+    #[test]
+    fn profiles() {
+        let text = r#"
+id = "com.example.game"
+name = "Game"
+author = "Author"
+version = "1.0.0+${GIT_SHA}"
+build = "${BUILD_NUMBER}"
+main_code = "main.bin"
+min_maikor_version = 1
+code_files = ["code.bin"]
+atlas_files = ["atlas.bin"]
+
+[profile.debug]
+id = "com.example.game_debug"
+name = "Game (Debug)"
+extra_code_files = ["debug.bin"]
+build = 1
+"#;
+        let manifest = Manifest::parse(text, ManifestFormat::Toml).unwrap();
+        assert_eq!(
+            manifest.build,
+            BuildNumber::Template(String::from("${BUILD_NUMBER}"))
+        );
+        assert_eq!(manifest.unresolved().len(), 2);
+
+        let release = manifest.resolve(None, vars).unwrap();
+        assert_eq!(release.build, BuildNumber::Number(42));
+        assert_eq!(release.version, "1.0.0+abc123");
+        assert_eq!(release.code_files, vec!["code.bin"]);
+        assert!(release.profiles.is_empty());
+        assert!(release.unresolved().is_empty());
+
+        let debug = manifest.resolve(Some("debug"), vars).unwrap();
+        assert_eq!(debug.id, "com.example.game_debug");
+        assert_eq!(debug.name, "Game (Debug)");
+        assert_eq!(debug.build, BuildNumber::Number(1));
+        assert_eq!(debug.code_files, vec!["code.bin", "debug.bin"]);
+        assert_eq!(debug.atlas_files, vec!["atlas.bin"]);
+
+        assert!(manifest.resolve(Some("beta"), vars).is_err());
+        assert!(manifest.resolve(None, |_| None).is_err());
+        assert!(manifest.resolve(None, |_| Some(String::from("x"))).is_err());
+    }
+}
@@ -0,0 +1,458 @@
+use crate::file_utils::{read_header_string, ReaderExt};
+use crate::read_write_impl::{Readable, Writeable};
+use crate::GameFileError;
+use crate::GameFileError::{FieldTooLong, FileAccessError, InvalidMetadata, InvalidUtf8};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::str::FromStr;
+
+pub const KEY_DESCRIPTION: &str = "description";
+pub const KEY_GENRES: &str = "genres";
+pub const KEY_TAGS: &str = "tags";
+pub const KEY_RELEASE_DATE: &str = "release_date";
+pub const KEY_AGE_RATING: &str = "age_rating";
+pub const KEY_HOMEPAGE: &str = "homepage";
+pub const KEY_LICENSE: &str = "license";
+pub const KEY_INPUTS: &str = "inputs";
+const WELL_KNOWN_KEYS: [&str; 8] = [
+    KEY_DESCRIPTION,
+    KEY_GENRES,
+    KEY_TAGS,
+    KEY_RELEASE_DATE,
+    KEY_AGE_RATING,
+    KEY_HOMEPAGE,
+    KEY_LICENSE,
+    KEY_INPUTS,
+];
+const MAX_KEY_LEN: usize = u8::MAX as usize;
+const MAX_VALUE_LEN: usize = u16::MAX as usize;
+///Separator for list values
+const LIST_SEPARATOR: char = '\n';
+
+/// Optional store/launcher information about a game
+///
+/// Stored in the game file as a metadata section of key/value pairs. Well known keys are
+/// parsed into typed fields and any other keys are kept in `custom`
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct GameMetadata {
+    ///Store description, max 65535 bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub genres: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<ReleaseDate>,
+    ///Minimum recommended player age in years
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_rating: Option<u8>,
+    ///Must be a http or https URL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(regex(pattern = "^https?://"))]
+    pub homepage: Option<String>,
+    ///License name, ideally an SPDX identifier such as `MIT`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    ///Input types the game supports
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<InputType>,
+    ///Any other keys, keys must be lowercase `a-z`, `0-9`, `_`, `.` and `-`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom: BTreeMap<String, String>,
+}
+
+#[derive(
+    Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum InputType {
+    Gamepad,
+    Keyboard,
+    Touch,
+}
+
+impl InputType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputType::Gamepad => "gamepad",
+            InputType::Keyboard => "keyboard",
+            InputType::Touch => "touch",
+        }
+    }
+}
+
+impl FromStr for InputType {
+    type Err = GameFileError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "gamepad" => Ok(InputType::Gamepad),
+            "keyboard" => Ok(InputType::Keyboard),
+            "touch" => Ok(InputType::Touch),
+            _ => Err(InvalidMetadata(format!("Unknown input type '{text}'"))),
+        }
+    }
+}
+
+/// Release date, written as `YYYY-MM-DD`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ReleaseDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl ReleaseDate {
+    pub fn new(year: u16, month: u8, day: u8) -> Result<Self, GameFileError> {
+        let days_in_month = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if year.is_multiple_of(4)
+                && (!year.is_multiple_of(100) || year.is_multiple_of(400)) =>
+            {
+                29
+            }
+            2 => 28,
+            _ => return Err(InvalidMetadata(format!("Invalid month {month}"))),
+        };
+        if day == 0 || day > days_in_month {
+            return Err(InvalidMetadata(format!(
+                "Invalid day {day} for {year}-{month:02}"
+            )));
+        }
+        Ok(Self { year, month, day })
+    }
+}
+
+impl FromStr for ReleaseDate {
+    type Err = GameFileError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = || InvalidMetadata(format!("Release date must be YYYY-MM-DD, was '{text}'"));
+        let parts: Vec<&str> = text.split('-').collect();
+        if parts.len() != 3
+            || parts[0].len() != 4
+            || parts[1].len() != 2
+            || parts[2].len() != 2
+            || !parts
+                .iter()
+                .all(|part| part.chars().all(|c| c.is_ascii_digit()))
+        {
+            return Err(error());
+        }
+        ReleaseDate::new(
+            parts[0].parse().map_err(|_| error())?,
+            parts[1].parse().map_err(|_| error())?,
+            parts[2].parse().map_err(|_| error())?,
+        )
+    }
+}
+
+impl Display for ReleaseDate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl Serialize for ReleaseDate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl JsonSchema for ReleaseDate {
+    fn schema_name() -> Cow<'static, str> {
+        "ReleaseDate".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "Release date, written as `YYYY-MM-DD`",
+            "type": "string",
+            "format": "date"
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ReleaseDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl GameMetadata {
+    pub fn is_empty(&self) -> bool {
+        self == &GameMetadata::default()
+    }
+
+    /// All values as key/value pairs, well known keys first, in the order they are written
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let mut entries = vec![];
+        let mut push = |key: &str, value: String| entries.push((key.to_string(), value));
+        if let Some(description) = &self.description {
+            push(KEY_DESCRIPTION, description.clone());
+        }
+        if !self.genres.is_empty() {
+            push(KEY_GENRES, join_list(&self.genres));
+        }
+        if !self.tags.is_empty() {
+            push(KEY_TAGS, join_list(&self.tags));
+        }
+        if let Some(date) = &self.release_date {
+            push(KEY_RELEASE_DATE, date.to_string());
+        }
+        if let Some(age) = self.age_rating {
+            push(KEY_AGE_RATING, age.to_string());
+        }
+        if let Some(homepage) = &self.homepage {
+            push(KEY_HOMEPAGE, homepage.clone());
+        }
+        if let Some(license) = &self.license {
+            push(KEY_LICENSE, license.clone());
+        }
+        if !self.inputs.is_empty() {
+            let names: Vec<String> = self
+                .inputs
+                .iter()
+                .map(|input| input.name().to_string())
+                .collect();
+            push(KEY_INPUTS, join_list(&names));
+        }
+        for (key, value) in &self.custom {
+            push(key, value.clone());
+        }
+        entries
+    }
+
+    /// Build from key/value pairs, well known keys are parsed and the rest are added to `custom`
+    pub fn from_entries<I: IntoIterator<Item = (String, String)>>(
+        entries: I,
+    ) -> Result<Self, GameFileError> {
+        let mut metadata = GameMetadata::default();
+        for (key, value) in entries {
+            match key.as_str() {
+                KEY_DESCRIPTION => metadata.description = Some(value),
+                KEY_GENRES => metadata.genres = split_list(&value),
+                KEY_TAGS => metadata.tags = split_list(&value),
+                KEY_RELEASE_DATE => metadata.release_date = Some(value.parse()?),
+                KEY_AGE_RATING => {
+                    metadata.age_rating = Some(value.parse().map_err(|_| {
+                        InvalidMetadata(format!("Age rating must be 0-255, was '{value}'"))
+                    })?)
+                }
+                KEY_HOMEPAGE => metadata.homepage = Some(value),
+                KEY_LICENSE => metadata.license = Some(value),
+                KEY_INPUTS => {
+                    metadata.inputs = split_list(&value)
+                        .iter()
+                        .map(|name| name.parse())
+                        .collect::<Result<_, _>>()?
+                }
+                _ => {
+                    metadata.custom.insert(key, value);
+                }
+            }
+        }
+        Ok(metadata)
+    }
+
+    /// Check values can be written and are in the expected format
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        if let Some(homepage) = &self.homepage {
+            if !(homepage.starts_with("https://") || homepage.starts_with("http://")) {
+                errors.push(format!("Homepage must be a http(s) URL, was '{homepage}'"));
+            }
+        }
+        for list in [&self.genres, &self.tags] {
+            for item in list {
+                if item.trim().is_empty() || item.contains(LIST_SEPARATOR) {
+                    errors.push(format!(
+                        "Genres and tags can't be empty or multiline: '{item}'"
+                    ));
+                }
+            }
+        }
+        for key in self.custom.keys() {
+            if WELL_KNOWN_KEYS.contains(&key.as_str()) {
+                errors.push(format!("Custom key '{key}' is a well known key"));
+            } else if key.is_empty()
+                || !key.chars().all(|c| {
+                    c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '.' | '-')
+                })
+            {
+                errors.push(format!(
+                    "Custom key '{key}' must only contain a-z, 0-9, _, . and -"
+                ));
+            } else if key.len() > MAX_KEY_LEN {
+                errors.push(format!(
+                    "Custom key '{key}' is longer than {MAX_KEY_LEN} bytes"
+                ));
+            }
+        }
+        let entries = self.entries();
+        if entries.len() > u16::MAX as usize {
+            errors.push(format!(
+                "Too many metadata entries, max is {} and was {}",
+                u16::MAX,
+                entries.len()
+            ));
+        }
+        for (key, value) in entries {
+            if value.len() > MAX_VALUE_LEN {
+                errors.push(format!(
+                    "Metadata {key} is too long, max is {MAX_VALUE_LEN} bytes and was {}",
+                    value.len()
+                ));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn join_list(items: &[String]) -> String {
+    items.join(&LIST_SEPARATOR.to_string())
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split(LIST_SEPARATOR).map(String::from).collect()
+}
+
+impl Writeable for GameMetadata {
+    fn as_bytes(&self) -> Result<Vec<u8>, GameFileError> {
+        let entries = self.entries();
+        if entries.len() > u16::MAX as usize {
+            return Err(InvalidMetadata(format!(
+                "Too many entries, max is {}",
+                u16::MAX
+            )));
+        }
+        let mut output = vec![];
+        output.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+        for (key, value) in entries {
+            if key.len() > MAX_KEY_LEN {
+                return Err(FieldTooLong("Metadata key", MAX_KEY_LEN, key.len()));
+            }
+            if value.len() > MAX_VALUE_LEN {
+                return Err(FieldTooLong("Metadata value", MAX_VALUE_LEN, value.len()));
+            }
+            output.push(key.len() as u8);
+            output.extend_from_slice(key.as_bytes());
+            output.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            output.extend_from_slice(value.as_bytes());
+        }
+        Ok(output)
+    }
+}
+
+impl Readable for GameMetadata {
+    fn from_reader<R: ReaderExt>(reader: &mut R) -> Result<Self, GameFileError>
+    where
+        Self: Sized,
+    {
+        let count = reader
+            .read_u16()
+            .map_err(|e| FileAccessError(e, "reading metadata count"))?;
+        let mut entries = vec![];
+        for _ in 0..count {
+            let key = read_header_string(reader, "Metadata key", "reading metadata key")?;
+            let len = reader
+                .read_u16()
+                .map_err(|e| FileAccessError(e, "reading metadata value length"))?;
+            let value = reader.read_string(len as usize).map_err(|e| {
+                if e.kind() == io::ErrorKind::InvalidData {
+                    InvalidUtf8("Metadata value")
+                } else {
+                    FileAccessError(e, "reading metadata value")
+                }
+            })?;
+            entries.push((key, value));
+        }
+        GameMetadata::from_entries(entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::BufReader;
+
+    fn metadata() -> GameMetadata {
+        let mut metadata = GameMetadata {
+            description: Some(String::from("A game\nabout things")),
+            genres: vec![String::from("Puzzle"), String::from("Action")],
+            tags: vec![],
+            release_date: Some(ReleaseDate::new(2024, 2, 29).unwrap()),
+            age_rating: Some(12),
+            homepage: Some(String::from("https://example.com")),
+            license: Some(String::from("MIT")),
+            inputs: vec![InputType::Gamepad, InputType::Touch],
+            custom: BTreeMap::new(),
+        };
+        metadata
+            .custom
+            .insert(String::from("x.store_id"), String::from("1234"));
+        metadata
+    }
+
+    #[test]
+    fn read_write() {
+        let metadata = metadata();
+        assert!(metadata.validate().is_ok());
+        let bytes = metadata.as_bytes().unwrap();
+        assert_eq!(bytes[..2], [0, 8]);
+        let parsed = GameMetadata::from_reader(&mut BufReader::new(&*bytes)).unwrap();
+        assert_eq!(parsed, metadata);
+    }
+
+    #[test]
+    fn json() {
+        let json =
+            r#"{"description": "Game", "release_date": "2023-11-05", "inputs": ["keyboard"]}"#;
+        let metadata: GameMetadata = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            metadata.release_date,
+            Some(ReleaseDate::new(2023, 11, 5).unwrap())
+        );
+        assert_eq!(metadata.inputs, vec![InputType::Keyboard]);
+        assert_eq!(
+            serde_json::to_string(&metadata).unwrap(),
+            r#"{"description":"Game","release_date":"2023-11-05","inputs":["keyboard"]}"#
+        );
+        assert!(serde_json::from_str::<GameMetadata>(r#"{"release_date": "2023-02-30"}"#).is_err());
+    }
+
+    #[test]
+    fn validate() {
+        let mut metadata = metadata();
+        metadata.homepage = Some(String::from("example.com"));
+        metadata
+            .custom
+            .insert(String::from(KEY_LICENSE), String::from("GPL"));
+        metadata
+            .custom
+            .insert(String::from("Bad Key"), String::from(""));
+        assert_eq!(metadata.validate().unwrap_err().len(), 3);
+
+        let mut too_many = GameMetadata::default();
+        for i in 0..=u16::MAX {
+            too_many.custom.insert(format!("key{i}"), String::new());
+        }
+        assert_eq!(too_many.validate().unwrap_err().len(), 1);
+        assert!(matches!(too_many.as_bytes(), Err(InvalidMetadata(_))));
+        assert!("2023-1-05".parse::<ReleaseDate>().is_err());
+        assert!("2023-13-05".parse::<ReleaseDate>().is_err());
+        assert!("2100-02-29".parse::<ReleaseDate>().is_err());
+    }
+}
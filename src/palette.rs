@@ -2,13 +2,15 @@ use crate::file_utils::{convert_vec, ReaderExt};
 use crate::read_write_impl::{FileReadable, Readable, Writeable};
 use crate::GameFileError;
 use crate::GameFileError::{FileAccessError, InvalidPalette};
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 
 const PALETTE_HEADER: [u8; 2] = [0xFD, 0xA2];
 pub const PALETTE_EXT: &str = "mpal";
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Palette {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub filepath: Option<String>,
     pub colors: [Color; 16],
 }
@@ -89,7 +91,9 @@ impl Readable for Palette {
     }
 }
 
-impl FileReadable for Palette {}
+impl FileReadable for Palette {
+    const CHECK_SIZE: bool = false;
+}
 
 #[cfg(test)]
 mod test {
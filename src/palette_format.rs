@@ -0,0 +1,398 @@
+use crate::file_utils::convert_vec;
+use crate::palette::{Color, Palette, PALETTE_EXT};
+use crate::read_write_impl::{validate_file, Readable, Writeable};
+use crate::GameFileError;
+use crate::GameFileError::{FileAccessError, InvalidPalette, PaletteColorCount};
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+
+const PALETTE_SIZE: usize = 16;
+const GIMP_HEADER: &str = "GIMP Palette";
+const JASC_HEADER: &str = "JASC-PAL";
+const JASC_VERSION: &str = "0100";
+const ACT_COLOR_COUNT: usize = 256;
+const ACT_SIZE: usize = ACT_COLOR_COUNT * 3;
+const ACT_NO_TRANSPARENCY: u16 = 0xFFFF;
+
+/// Supported palette file formats
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PaletteFormat {
+    ///Maikor palette (`.mpal`)
+    Maikor,
+    ///GIMP palette (`.gpl`)
+    Gimp,
+    ///JASC-PAL, used by Paint Shop Pro and others (`.pal`)
+    Jasc,
+    ///Adobe Color Table (`.act`)
+    Act,
+    ///One color per line as `RRGGBB`/`#RRGGBB`/`AARRGGBB` (`.hex`, `.txt`)
+    Hex,
+}
+
+impl PaletteFormat {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<PaletteFormat> {
+        let ext = path.as_ref().extension()?.to_string_lossy().to_lowercase();
+        match ext.as_str() {
+            PALETTE_EXT => Some(PaletteFormat::Maikor),
+            "gpl" => Some(PaletteFormat::Gimp),
+            "pal" => Some(PaletteFormat::Jasc),
+            "act" => Some(PaletteFormat::Act),
+            "hex" | "txt" => Some(PaletteFormat::Hex),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PaletteFormat::Maikor => PALETTE_EXT,
+            PaletteFormat::Gimp => "gpl",
+            PaletteFormat::Jasc => "pal",
+            PaletteFormat::Act => "act",
+            PaletteFormat::Hex => "hex",
+        }
+    }
+}
+
+/// How to handle source palettes that don't have exactly 16 colors
+///
+/// By default palettes with more or fewer colors are rejected
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+pub struct PaletteImportOptions {
+    ///Drop colors after the 16th
+    pub truncate: bool,
+    ///Fill missing colors with this color
+    pub pad: Option<Color>,
+}
+
+impl PaletteImportOptions {
+    pub fn new(truncate: bool, pad: Option<Color>) -> Self {
+        Self { truncate, pad }
+    }
+}
+
+impl Palette {
+    /// Parse a palette from `bytes` in `format`
+    pub fn import(
+        bytes: &[u8],
+        format: PaletteFormat,
+        options: &PaletteImportOptions,
+    ) -> Result<Palette, GameFileError> {
+        let colors = match format {
+            PaletteFormat::Maikor => {
+                return Palette::from_reader(&mut BufReader::new(bytes));
+            }
+            PaletteFormat::Gimp => parse_gimp(&as_text(bytes)?)?,
+            PaletteFormat::Jasc => parse_jasc(&as_text(bytes)?)?,
+            PaletteFormat::Act => parse_act(bytes)?,
+            PaletteFormat::Hex => parse_hex(&as_text(bytes)?)?,
+        };
+        Ok(Palette::new(None, fit_colors(colors, options)?))
+    }
+
+    /// Read a palette file, the format is detected from the file extension
+    pub fn import_file<P: AsRef<Path>>(
+        path: P,
+        options: &PaletteImportOptions,
+    ) -> Result<Palette, GameFileError> {
+        let path = path.as_ref();
+        let format = PaletteFormat::from_path(path)
+            .ok_or_else(|| InvalidPalette(format!("Unknown palette format: {}", path.display())))?;
+        validate_file(path, false)?;
+        let bytes = fs::read(path).map_err(|e| FileAccessError(e, "reading palette"))?;
+        let mut palette = Palette::import(&bytes, format, options)?;
+        palette.filepath = Some(path.to_string_lossy().to_string());
+        Ok(palette)
+    }
+
+    /// Convert this palette to `format`
+    pub fn export(&self, format: PaletteFormat) -> Result<Vec<u8>, GameFileError> {
+        let output = match format {
+            PaletteFormat::Maikor => return self.as_bytes(),
+            PaletteFormat::Gimp => {
+                let name = self
+                    .filename()
+                    .and_then(|name| name.split('.').next().map(String::from))
+                    .unwrap_or_else(|| String::from("Maikor"));
+                let mut text = format!("{GIMP_HEADER}\nName: {name}\nColumns: 16\n#\n");
+                for (i, color) in self.colors.iter().enumerate() {
+                    text.push_str(&format!(
+                        "{:>3} {:>3} {:>3}\tIndex {}\n",
+                        color.r, color.g, color.b, i
+                    ));
+                }
+                text.into_bytes()
+            }
+            PaletteFormat::Jasc => {
+                let mut text = format!("{JASC_HEADER}\r\n{JASC_VERSION}\r\n{PALETTE_SIZE}\r\n");
+                for color in &self.colors {
+                    text.push_str(&format!("{} {} {}\r\n", color.r, color.g, color.b));
+                }
+                text.into_bytes()
+            }
+            PaletteFormat::Act => {
+                let mut bytes = vec![0; ACT_SIZE];
+                for (i, color) in self.colors.iter().enumerate() {
+                    bytes[i * 3..i * 3 + 3].copy_from_slice(&color.as_bytes());
+                }
+                bytes.extend_from_slice(&(PALETTE_SIZE as u16).to_be_bytes());
+                bytes.extend_from_slice(&ACT_NO_TRANSPARENCY.to_be_bytes());
+                bytes
+            }
+            PaletteFormat::Hex => {
+                let mut text = String::new();
+                for color in &self.colors {
+                    text.push_str(&format!("{:02X}{:02X}{:02X}\n", color.r, color.g, color.b));
+                }
+                text.into_bytes()
+            }
+        };
+        Ok(output)
+    }
+
+    /// Write this palette to `path`, the format is detected from the file extension
+    pub fn export_file<P: AsRef<Path>>(&self, path: P) -> Result<(), GameFileError> {
+        let path = path.as_ref();
+        let format = PaletteFormat::from_path(path)
+            .ok_or_else(|| InvalidPalette(format!("Unknown palette format: {}", path.display())))?;
+        fs::write(path, self.export(format)?).map_err(|e| FileAccessError(e, "writing palette"))
+    }
+}
+
+fn fit_colors(
+    mut colors: Vec<Color>,
+    options: &PaletteImportOptions,
+) -> Result<[Color; 16], GameFileError> {
+    if colors.len() > PALETTE_SIZE {
+        if !options.truncate {
+            return Err(PaletteColorCount(colors.len()));
+        }
+        colors.truncate(PALETTE_SIZE);
+    }
+    if colors.len() < PALETTE_SIZE {
+        match options.pad {
+            None => return Err(PaletteColorCount(colors.len())),
+            Some(pad) => colors.resize(PALETTE_SIZE, pad),
+        }
+    }
+    Ok(convert_vec(colors))
+}
+
+fn as_text(bytes: &[u8]) -> Result<String, GameFileError> {
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| InvalidPalette(String::from("Palette is not valid text")))
+}
+
+fn parse_component(value: &str, line: usize) -> Result<u8, GameFileError> {
+    value
+        .parse::<u8>()
+        .map_err(|_| InvalidPalette(format!("Invalid color value '{value}' on line {line}")))
+}
+
+fn parse_rgb_line(text: &str, line: usize) -> Result<Color, GameFileError> {
+    let parts: Vec<&str> = text.split_whitespace().take(3).collect();
+    if parts.len() < 3 {
+        return Err(InvalidPalette(format!(
+            "Missing color values on line {line}"
+        )));
+    }
+    Ok(Color::new(
+        parse_component(parts[0], line)?,
+        parse_component(parts[1], line)?,
+        parse_component(parts[2], line)?,
+    ))
+}
+
+fn parse_gimp(text: &str) -> Result<Vec<Color>, GameFileError> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, header)) if header.trim() == GIMP_HEADER => {}
+        _ => return Err(InvalidPalette(String::from("Not a GIMP palette"))),
+    }
+    let mut colors = vec![];
+    for (i, line) in lines {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:")
+        {
+            continue;
+        }
+        colors.push(parse_rgb_line(line, i + 1)?);
+    }
+    Ok(colors)
+}
+
+fn parse_jasc(text: &str) -> Result<Vec<Color>, GameFileError> {
+    let mut lines = text.lines().map(str::trim).enumerate();
+    if lines.next().map(|(_, line)| line) != Some(JASC_HEADER) {
+        return Err(InvalidPalette(String::from("Not a JASC palette")));
+    }
+    if lines.next().map(|(_, line)| line) != Some(JASC_VERSION) {
+        return Err(InvalidPalette(String::from(
+            "Unsupported JASC palette version",
+        )));
+    }
+    let count = lines
+        .next()
+        .and_then(|(_, line)| line.parse::<usize>().ok())
+        .ok_or_else(|| InvalidPalette(String::from("Missing JASC palette color count")))?;
+    let mut colors = vec![];
+    for (i, line) in lines {
+        if line.is_empty() {
+            continue;
+        }
+        colors.push(parse_rgb_line(line, i + 1)?);
+    }
+    if colors.len() != count {
+        return Err(InvalidPalette(format!(
+            "JASC palette declares {} colors but has {}",
+            count,
+            colors.len()
+        )));
+    }
+    Ok(colors)
+}
+
+fn parse_act(bytes: &[u8]) -> Result<Vec<Color>, GameFileError> {
+    let count = match bytes.len() {
+        ACT_SIZE => ACT_COLOR_COUNT,
+        len if len == ACT_SIZE + 4 => {
+            let count = u16::from_be_bytes([bytes[ACT_SIZE], bytes[ACT_SIZE + 1]]) as usize;
+            count.min(ACT_COLOR_COUNT)
+        }
+        len => {
+            return Err(InvalidPalette(format!(
+                "ACT palette must be {} or {} bytes, was {}",
+                ACT_SIZE,
+                ACT_SIZE + 4,
+                len
+            )))
+        }
+    };
+    Ok(bytes[..count * 3]
+        .chunks_exact(3)
+        .map(|rgb| Color::new(rgb[0], rgb[1], rgb[2]))
+        .collect())
+}
+
+fn parse_hex(text: &str) -> Result<Vec<Color>, GameFileError> {
+    let mut colors = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with("//") {
+            continue;
+        }
+        let hex = line.trim_start_matches('#');
+        let rgb = match hex.len() {
+            6 => hex,
+            //Paint.NET format, AARRGGBB
+            8 => hex.get(2..).unwrap_or_default(),
+            _ => "",
+        };
+        let color = Color::from_hex(rgb).map_err(|_| {
+            InvalidPalette(format!("Invalid hex color '{}' on line {}", line, i + 1))
+        })?;
+        colors.push(color);
+    }
+    Ok(colors)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_palette() -> Palette {
+        let mut colors = [Color::new(0, 0, 0); 16];
+        for (i, color) in colors.iter_mut().enumerate() {
+            *color = Color::new(i as u8 * 16, 255 - i as u8, i as u8);
+        }
+        Palette::new(None, colors)
+    }
+
+    #[test]
+    fn round_trip_all_formats() {
+        let palette = test_palette();
+        for format in [
+            PaletteFormat::Maikor,
+            PaletteFormat::Gimp,
+            PaletteFormat::Jasc,
+            PaletteFormat::Act,
+            PaletteFormat::Hex,
+        ] {
+            let bytes = palette.export(format).unwrap();
+            let parsed = Palette::import(&bytes, format, &PaletteImportOptions::default()).unwrap();
+            assert_eq!(parsed, palette, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn parse_gimp_file() {
+        let text = "GIMP Palette\nName: Test\nColumns: 4\n# comment\n255   0   0\tRed\n  0 255   0\tGreen\n";
+        let colors = parse_gimp(text).unwrap();
+        assert_eq!(colors, vec![Color::new(255, 0, 0), Color::new(0, 255, 0)]);
+    }
+
+    #[test]
+    fn parse_hex_variants() {
+        let text = "; paint.net\nFF102030\n#405060\n708090\n";
+        let colors = parse_hex(text).unwrap();
+        assert_eq!(
+            colors,
+            vec![
+                Color::new(16, 32, 48),
+                Color::new(64, 80, 96),
+                Color::new(112, 128, 144)
+            ]
+        );
+        assert!(parse_hex("12345").is_err());
+    }
+
+    #[test]
+    fn color_count() {
+        let too_few = "000000\nFFFFFF\n".as_bytes();
+        let result = Palette::import(
+            too_few,
+            PaletteFormat::Hex,
+            &PaletteImportOptions::default(),
+        );
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Palette must have 16 colors but had 2"
+        );
+        let padded = Palette::import(
+            too_few,
+            PaletteFormat::Hex,
+            &PaletteImportOptions::new(false, Some(Color::new(1, 2, 3))),
+        )
+        .unwrap();
+        assert_eq!(padded.colors[1], Color::new(255, 255, 255));
+        assert_eq!(padded.colors[15], Color::new(1, 2, 3));
+
+        let act = vec![7; ACT_SIZE];
+        assert!(
+            Palette::import(&act, PaletteFormat::Act, &PaletteImportOptions::default()).is_err()
+        );
+        let truncated = Palette::import(
+            &act,
+            PaletteFormat::Act,
+            &PaletteImportOptions::new(true, None),
+        )
+        .unwrap();
+        assert_eq!(truncated.colors[15], Color::new(7, 7, 7));
+    }
+
+    #[test]
+    fn detect_format() {
+        assert_eq!(
+            PaletteFormat::from_path("a/b.GPL"),
+            Some(PaletteFormat::Gimp)
+        );
+        assert_eq!(
+            PaletteFormat::from_path("test.mpal"),
+            Some(PaletteFormat::Maikor)
+        );
+        assert_eq!(PaletteFormat::from_path("test.png"), None);
+    }
+}
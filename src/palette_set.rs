@@ -0,0 +1,221 @@
+use crate::file_utils::{convert_vec, read_header_string, ReaderExt};
+use crate::game_header::convert_string;
+use crate::palette::{Color, Palette};
+use crate::palette_format::PaletteImportOptions;
+use crate::read_write_impl::{FileReadable, Readable, Writeable};
+use crate::GameFileError::{FileAccessError, InvalidPalette};
+use crate::{GameFileError, MAX_STRING_LEN};
+use std::path::Path;
+
+const PALETTE_SET_HEADER: [u8; 2] = [0xFD, 0xA3];
+pub const PALETTE_SET_EXT: &str = "mpals";
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct NamedPalette {
+    pub name: String,
+    pub palette: Palette,
+}
+
+/// Collection of named palettes, names must be unique
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct PaletteSet {
+    palettes: Vec<NamedPalette>,
+}
+
+impl PaletteSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a set from palette files (in any supported format), each palette is named after
+    /// the file name without extension
+    pub fn from_files<P: AsRef<Path>>(paths: &[P]) -> Result<PaletteSet, GameFileError> {
+        let mut set = PaletteSet::new();
+        for path in paths {
+            let path = path.as_ref();
+            let name = path
+                .file_stem()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let palette = Palette::import_file(path, &PaletteImportOptions::default())?;
+            set.add(name, palette)?;
+        }
+        Ok(set)
+    }
+}
+
+impl PaletteSet {
+    /// Names are trimmed when written, so `"day"` and `" day "` are duplicates
+    pub fn add(&mut self, name: String, palette: Palette) -> Result<(), GameFileError> {
+        if name.trim().len() > MAX_STRING_LEN {
+            return Err(InvalidPalette(format!(
+                "Palette name '{name}' is longer than {MAX_STRING_LEN} bytes"
+            )));
+        }
+        if self
+            .palettes
+            .iter()
+            .any(|named| named.name.trim() == name.trim())
+        {
+            return Err(InvalidPalette(format!("Duplicate palette name: {name}")));
+        }
+        self.palettes.push(NamedPalette { name, palette });
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Palette> {
+        self.palettes
+            .iter()
+            .find(|named| named.name == name)
+            .map(|named| &named.palette)
+    }
+
+    pub fn palettes(&self) -> &[NamedPalette] {
+        &self.palettes
+    }
+
+    pub fn len(&self) -> usize {
+        self.palettes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.palettes.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut error = vec![];
+        if self.palettes.len() > u8::MAX as usize {
+            error.push(String::from(
+                "Palette set has too many palettes, max is 255",
+            ));
+        }
+        for (i, named) in self.palettes.iter().enumerate() {
+            let name = named.name.trim();
+            if name.is_empty() {
+                error.push(String::from(
+                    "Palette name must have at least one character",
+                ));
+            } else if name.len() > MAX_STRING_LEN {
+                error.push(format!(
+                    "Palette name '{name}' is longer than {MAX_STRING_LEN} bytes"
+                ));
+            }
+            if self.palettes[..i]
+                .iter()
+                .any(|other| other.name.trim() == name)
+            {
+                error.push(format!("Duplicate palette name: {name}"));
+            }
+        }
+        if error.is_empty() {
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+}
+
+impl Writeable for PaletteSet {
+    fn as_bytes(&self) -> Result<Vec<u8>, GameFileError> {
+        if self.palettes.len() > u8::MAX as usize {
+            return Err(InvalidPalette(String::from(
+                "Palette set has too many palettes, max is 255",
+            )));
+        }
+        let mut output = vec![];
+        output.extend_from_slice(&PALETTE_SET_HEADER);
+        output.push(self.palettes.len() as u8);
+        for named in &self.palettes {
+            output.extend_from_slice(&convert_string("Palette name", &named.name)?);
+            for color in named.palette.colors {
+                output.extend_from_slice(&color.as_bytes());
+            }
+        }
+        Ok(output)
+    }
+}
+
+impl Readable for PaletteSet {
+    fn from_reader<R: ReaderExt>(reader: &mut R) -> Result<Self, GameFileError>
+    where
+        Self: Sized,
+    {
+        let mut header = [0; 2];
+        reader
+            .read_exact(&mut header)
+            .map_err(|e| FileAccessError(e, "reading palette set header data"))?;
+        if header != PALETTE_SET_HEADER {
+            return Err(InvalidPalette(String::from("Not a palette set file")));
+        }
+        let count = reader
+            .read_u8()
+            .map_err(|e| FileAccessError(e, "reading palette count"))?;
+        let mut set = PaletteSet::new();
+        for _ in 0..count {
+            let name = read_header_string(reader, "Palette name", "reading palette name")?;
+            let blocks = reader
+                .read_multiple_blocks(3, 16)
+                .map_err(|e| FileAccessError(e, "reading palette data"))?;
+            let colors: Vec<Color> = blocks
+                .into_iter()
+                .map(|rgb| Color::from_bytes(convert_vec(rgb)))
+                .collect();
+            set.add(name, Palette::new(None, convert_vec(colors)))?;
+        }
+        Ok(set)
+    }
+}
+
+impl FileReadable for PaletteSet {
+    const CHECK_SIZE: bool = false;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn read_write() {
+        let mut set = PaletteSet::new();
+        set.add(
+            String::from("day"),
+            Palette::new(None, [Color::new(1, 2, 3); 16]),
+        )
+        .unwrap();
+        set.add(
+            String::from("night"),
+            Palette::new(None, [Color::new(4, 5, 6); 16]),
+        )
+        .unwrap();
+        assert!(set
+            .add(
+                String::from("day"),
+                Palette::new(None, [Color::new(0, 0, 0); 16])
+            )
+            .is_err());
+        assert!(set
+            .add(
+                String::from(" night "),
+                Palette::new(None, [Color::new(0, 0, 0); 16])
+            )
+            .is_err());
+        assert!(set
+            .add(
+                "x".repeat(MAX_STRING_LEN + 1),
+                Palette::new(None, [Color::new(0, 0, 0); 16])
+            )
+            .is_err());
+        assert_eq!(set.validate(), Ok(()));
+        let mut duplicate = set.clone();
+        duplicate.palettes[1].name = String::from("day ");
+        assert_eq!(duplicate.validate().unwrap_err().len(), 1);
+
+        let bytes = set.as_bytes().unwrap();
+        assert_eq!(bytes.len(), 3 + (4 + 48) + (6 + 48));
+        let parsed = PaletteSet::from_reader(&mut BufReader::new(&*bytes)).unwrap();
+        assert_eq!(parsed, set);
+        assert_eq!(parsed.get("night").unwrap().colors[0], Color::new(4, 5, 6));
+        assert!(parsed.get("evening").is_none());
+    }
+}
@@ -0,0 +1,496 @@
+use crate::checksum::crc32;
+use crate::constants::mem::{ATLAS_BANK, CODE_BANK, MAIN_CODE};
+use crate::file_utils::ReaderExt;
+use crate::read_write_impl::{FileReadable, Readable, Writeable};
+use crate::GameFileError::{FileAccessError, InvalidPatch, PatchNotApplicable};
+use crate::{GameFile, GameFileError, GameFileHeader, MAX_FILE_SIZE};
+use std::io::BufReader;
+
+const PATCH_HEADER: [u8; 2] = [0xFD, 0xA4];
+const PATCH_FORMAT_VER: u8 = 1;
+pub const PATCH_EXT: &str = "mpatch";
+///Unchanged runs shorter than this are included in a delta rather than starting a new one
+///(as each delta has 8 bytes of overhead)
+const MIN_GAP: usize = 8;
+
+/// Part of a game file
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PatchSection {
+    Header,
+    MainCode,
+    CodeBank(u8),
+    AtlasBank(u8),
+    ///All optional sections (palettes, etc)
+    Sections,
+}
+
+impl PatchSection {
+    fn as_bytes(&self) -> [u8; 2] {
+        match self {
+            PatchSection::Header => [0, 0],
+            PatchSection::MainCode => [1, 0],
+            PatchSection::CodeBank(idx) => [2, *idx],
+            PatchSection::AtlasBank(idx) => [3, *idx],
+            PatchSection::Sections => [4, 0],
+        }
+    }
+
+    fn from_bytes(kind: u8, idx: u8) -> Result<PatchSection, GameFileError> {
+        match kind {
+            0 => Ok(PatchSection::Header),
+            1 => Ok(PatchSection::MainCode),
+            2 => Ok(PatchSection::CodeBank(idx)),
+            3 => Ok(PatchSection::AtlasBank(idx)),
+            4 => Ok(PatchSection::Sections),
+            _ => Err(InvalidPatch(format!("Unknown section type {kind}"))),
+        }
+    }
+
+    /// Largest valid length of this section in a game file
+    fn max_len(&self) -> u64 {
+        match self {
+            PatchSection::MainCode => MAIN_CODE as u64,
+            PatchSection::CodeBank(_) => CODE_BANK as u64,
+            PatchSection::AtlasBank(_) => ATLAS_BANK as u64,
+            PatchSection::Header | PatchSection::Sections => MAX_FILE_SIZE,
+        }
+    }
+
+    fn check_len(&self, len: u32) -> Result<(), GameFileError> {
+        if len as u64 > self.max_len() {
+            return Err(InvalidPatch(format!(
+                "{self:?} is {len} bytes, max is {}",
+                self.max_len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Bytes to write at `offset`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ByteDelta {
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// Changes for a single section, sections not in a patch are unchanged
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SectionPatch {
+    pub section: PatchSection,
+    ///Length of section in target
+    pub target_len: u32,
+    pub deltas: Vec<ByteDelta>,
+}
+
+/// Difference between two builds of a game file
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GamePatch {
+    ///CRC32 of the whole source file
+    pub source_checksum: u32,
+    ///CRC32 of the whole target file
+    pub target_checksum: u32,
+    pub source_build: u32,
+    pub target_build: u32,
+    pub sections: Vec<SectionPatch>,
+}
+
+/// Game file split into patchable sections
+struct Segments {
+    header: Vec<u8>,
+    main_code: Vec<u8>,
+    code_banks: Vec<Vec<u8>>,
+    atlases: Vec<Vec<u8>>,
+    sections: Vec<u8>,
+}
+
+impl Segments {
+    fn new(file: &GameFile) -> Result<Segments, GameFileError> {
+        Ok(Segments {
+            header: file.header.as_bytes()?,
+            main_code: file.main_code.to_vec(),
+            code_banks: file.code_banks.iter().map(|bank| bank.to_vec()).collect(),
+            atlases: file.atlases.iter().map(|bank| bank.to_vec()).collect(),
+            sections: file.sections_as_bytes()?,
+        })
+    }
+
+    fn get(&self, section: PatchSection) -> &[u8] {
+        match section {
+            PatchSection::Header => &self.header,
+            PatchSection::MainCode => &self.main_code,
+            PatchSection::CodeBank(idx) => self
+                .code_banks
+                .get(idx as usize)
+                .map(|bank| bank.as_slice())
+                .unwrap_or_default(),
+            PatchSection::AtlasBank(idx) => self
+                .atlases
+                .get(idx as usize)
+                .map(|bank| bank.as_slice())
+                .unwrap_or_default(),
+            PatchSection::Sections => &self.sections,
+        }
+    }
+
+    fn list(&self) -> Vec<PatchSection> {
+        let mut list = vec![PatchSection::Header, PatchSection::MainCode];
+        list.extend((0..self.code_banks.len()).map(|i| PatchSection::CodeBank(i as u8)));
+        list.extend((0..self.atlases.len()).map(|i| PatchSection::AtlasBank(i as u8)));
+        list.push(PatchSection::Sections);
+        list
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut output = self.header.clone();
+        output.extend_from_slice(&self.main_code);
+        self.code_banks
+            .iter()
+            .for_each(|bank| output.extend_from_slice(bank));
+        self.atlases
+            .iter()
+            .for_each(|bank| output.extend_from_slice(bank));
+        output.extend_from_slice(&self.sections);
+        output
+    }
+}
+
+impl GamePatch {
+    /// Create patch that converts `source` into `target`
+    pub fn diff(source: &GameFile, target: &GameFile) -> Result<GamePatch, GameFileError> {
+        let source_segments = Segments::new(source)?;
+        let target_segments = Segments::new(target)?;
+        let mut sections = vec![];
+        for section in target_segments.list() {
+            let old = source_segments.get(section);
+            let new = target_segments.get(section);
+            if old != new {
+                sections.push(SectionPatch {
+                    section,
+                    target_len: new.len() as u32,
+                    deltas: diff_bytes(old, new),
+                });
+            }
+        }
+        Ok(GamePatch {
+            source_checksum: crc32(&source_segments.to_bytes()),
+            target_checksum: crc32(&target_segments.to_bytes()),
+            source_build: source.header.build,
+            target_build: target.header.build,
+            sections,
+        })
+    }
+
+    /// Apply patch to `source`, fails if `source` isn't the file the patch was made from
+    pub fn apply(&self, source: &GameFile) -> Result<GameFile, GameFileError> {
+        if source.header.build != self.source_build {
+            return Err(PatchNotApplicable(format!(
+                "patch is for build {} but file is build {}",
+                self.source_build, source.header.build
+            )));
+        }
+        let source_segments = Segments::new(source)?;
+        if crc32(&source_segments.to_bytes()) != self.source_checksum {
+            return Err(PatchNotApplicable(String::from(
+                "file content doesn't match patch source",
+            )));
+        }
+
+        let header = self.patch_section(&source_segments, PatchSection::Header)?;
+        let parsed_header = GameFileHeader::from_reader(&mut BufReader::new(&*header))?;
+        let target_segments = Segments {
+            main_code: self.patch_section(&source_segments, PatchSection::MainCode)?,
+            code_banks: (0..parsed_header.code_bank_count)
+                .map(|i| self.patch_section(&source_segments, PatchSection::CodeBank(i)))
+                .collect::<Result<_, _>>()?,
+            atlases: (0..parsed_header.atlas_bank_count)
+                .map(|i| self.patch_section(&source_segments, PatchSection::AtlasBank(i)))
+                .collect::<Result<_, _>>()?,
+            sections: self.patch_section(&source_segments, PatchSection::Sections)?,
+            header,
+        };
+
+        let bytes = target_segments.to_bytes();
+        if crc32(&bytes) != self.target_checksum {
+            return Err(PatchNotApplicable(String::from(
+                "patched content doesn't match patch target",
+            )));
+        }
+        GameFile::from_reader(&mut BufReader::new(&*bytes))
+    }
+
+    fn patch_section(
+        &self,
+        source: &Segments,
+        section: PatchSection,
+    ) -> Result<Vec<u8>, GameFileError> {
+        let mut bytes = source.get(section).to_vec();
+        if let Some(patch) = self.sections.iter().find(|patch| patch.section == section) {
+            section.check_len(patch.target_len)?;
+            bytes.resize(patch.target_len as usize, 0);
+            for delta in &patch.deltas {
+                let start = delta.offset as usize;
+                let end = start + delta.bytes.len();
+                if end > bytes.len() {
+                    return Err(InvalidPatch(format!(
+                        "delta for {:?} ends at {} but section is {} bytes",
+                        section,
+                        end,
+                        bytes.len()
+                    )));
+                }
+                bytes[start..end].copy_from_slice(&delta.bytes);
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+/// Find the runs of bytes in `new` that differ from `old`
+fn diff_bytes(old: &[u8], new: &[u8]) -> Vec<ByteDelta> {
+    let mut deltas: Vec<ByteDelta> = vec![];
+    let mut last_end = 0;
+    let mut i = 0;
+    while i < new.len() {
+        if old.get(i) == Some(&new[i]) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < new.len() && old.get(i) != Some(&new[i]) {
+            i += 1;
+        }
+        match deltas.last_mut() {
+            Some(last) if start - last_end < MIN_GAP => {
+                last.bytes.extend_from_slice(&new[last_end..i]);
+            }
+            _ => deltas.push(ByteDelta {
+                offset: start as u32,
+                bytes: new[start..i].to_vec(),
+            }),
+        }
+        last_end = i;
+    }
+    deltas
+}
+
+impl Writeable for GamePatch {
+    fn as_bytes(&self) -> Result<Vec<u8>, GameFileError> {
+        let mut output = vec![];
+        output.extend_from_slice(&PATCH_HEADER);
+        output.push(PATCH_FORMAT_VER);
+        output.extend_from_slice(&self.source_checksum.to_be_bytes());
+        output.extend_from_slice(&self.target_checksum.to_be_bytes());
+        output.extend_from_slice(&self.source_build.to_be_bytes());
+        output.extend_from_slice(&self.target_build.to_be_bytes());
+        output.extend_from_slice(&(self.sections.len() as u16).to_be_bytes());
+        for section in &self.sections {
+            output.extend_from_slice(&section.section.as_bytes());
+            output.extend_from_slice(&section.target_len.to_be_bytes());
+            output.extend_from_slice(&(section.deltas.len() as u32).to_be_bytes());
+            for delta in &section.deltas {
+                output.extend_from_slice(&delta.offset.to_be_bytes());
+                output.extend_from_slice(&(delta.bytes.len() as u32).to_be_bytes());
+                output.extend_from_slice(&delta.bytes);
+            }
+        }
+        Ok(output)
+    }
+}
+
+impl Readable for GamePatch {
+    fn from_reader<R: ReaderExt>(reader: &mut R) -> Result<Self, GameFileError>
+    where
+        Self: Sized,
+    {
+        let mut header = [0; 2];
+        reader
+            .read_exact(&mut header)
+            .map_err(|e| FileAccessError(e, "reading patch header"))?;
+        if header != PATCH_HEADER {
+            return Err(InvalidPatch(String::from("Not a patch file")));
+        }
+        let version = reader
+            .read_u8()
+            .map_err(|e| FileAccessError(e, "reading patch version"))?;
+        if version != PATCH_FORMAT_VER {
+            return Err(InvalidPatch(format!(
+                "Unsupported patch version, was {version} and must be {PATCH_FORMAT_VER}"
+            )));
+        }
+        let read_u32 = |reader: &mut R, field: &'static str| {
+            reader.read_u32().map_err(|e| FileAccessError(e, field))
+        };
+        let source_checksum = read_u32(reader, "reading source checksum")?;
+        let target_checksum = read_u32(reader, "reading target checksum")?;
+        let source_build = read_u32(reader, "reading source build")?;
+        let target_build = read_u32(reader, "reading target build")?;
+        let section_count = reader
+            .read_u16()
+            .map_err(|e| FileAccessError(e, "reading section count"))?;
+        let mut sections = vec![];
+        for _ in 0..section_count {
+            let kind = reader
+                .read_u8()
+                .map_err(|e| FileAccessError(e, "reading section type"))?;
+            let idx = reader
+                .read_u8()
+                .map_err(|e| FileAccessError(e, "reading section index"))?;
+            let section = PatchSection::from_bytes(kind, idx)?;
+            let target_len = read_u32(reader, "reading section length")?;
+            //deltas must be inside the section, so this also limits their length
+            section.check_len(target_len)?;
+            let delta_count = read_u32(reader, "reading delta count")?;
+            let mut deltas = vec![];
+            for _ in 0..delta_count {
+                let offset = read_u32(reader, "reading delta offset")?;
+                let len = read_u32(reader, "reading delta length")?;
+                if offset as u64 + len as u64 > target_len as u64 {
+                    return Err(InvalidPatch(String::from("Delta is outside of section")));
+                }
+                let bytes = reader
+                    .read_block(len as usize)
+                    .map_err(|e| FileAccessError(e, "reading delta"))?;
+                deltas.push(ByteDelta { offset, bytes });
+            }
+            sections.push(SectionPatch {
+                section,
+                target_len,
+                deltas,
+            });
+        }
+        Ok(GamePatch {
+            source_checksum,
+            target_checksum,
+            source_build,
+            target_build,
+            sections,
+        })
+    }
+}
+
+impl FileReadable for GamePatch {
+    const CHECK_SIZE: bool = false;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::constants::mem::{ATLAS_BANK, CODE_BANK, MAIN_CODE};
+
+    fn make_file(build: u32, code_banks: usize) -> GameFile {
+        let header = GameFileHeader::new(
+            String::from("com.example.game"),
+            build,
+            1,
+            1,
+            0,
+            String::from("Game"),
+            format!("1.{build}"),
+            String::from("Author"),
+            code_banks as u8,
+            1,
+        );
+        GameFile::new(
+            header,
+            [1; MAIN_CODE],
+            vec![[2; CODE_BANK]; code_banks],
+            vec![[3; ATLAS_BANK]],
+        )
+    }
+
+    #[test]
+    fn diff_bytes_runs() {
+        let old = [0; 32];
+        let mut new = [0; 32];
+        new[1] = 1;
+        new[4] = 1;
+        new[20] = 1;
+        let deltas = diff_bytes(&old, &new);
+        assert_eq!(
+            deltas,
+            vec![
+                ByteDelta {
+                    offset: 1,
+                    bytes: vec![1, 0, 0, 1]
+                },
+                ByteDelta {
+                    offset: 20,
+                    bytes: vec![1]
+                }
+            ]
+        );
+        assert_eq!(diff_bytes(&[1, 2], &[1, 2, 3]).len(), 1);
+    }
+
+    #[test]
+    fn diff_and_apply() {
+        let source = make_file(1, 1);
+        let mut target = make_file(2, 2);
+        target.code_banks[1][100] = 9;
+        target.atlases[0][5] = 7;
+
+        let patch = GamePatch::diff(&source, &target).unwrap();
+        let sections: Vec<PatchSection> = patch.sections.iter().map(|s| s.section).collect();
+        assert_eq!(
+            sections,
+            vec![
+                PatchSection::Header,
+                PatchSection::CodeBank(1),
+                PatchSection::AtlasBank(0)
+            ]
+        );
+
+        let bytes = patch.as_bytes().unwrap();
+        assert!(bytes.len() < CODE_BANK + 100);
+        let parsed = GamePatch::from_reader(&mut BufReader::new(&*bytes)).unwrap();
+        assert_eq!(parsed, patch);
+
+        let patched = parsed.apply(&source).unwrap();
+        assert_eq!(patched.as_bytes().unwrap(), target.as_bytes().unwrap());
+
+        let reverse = GamePatch::diff(&target, &source).unwrap();
+        let unpatched = reverse.apply(&target).unwrap();
+        assert_eq!(unpatched.as_bytes().unwrap(), source.as_bytes().unwrap());
+    }
+
+    #[test]
+    fn wrong_base() {
+        let source = make_file(1, 1);
+        let target = make_file(2, 1);
+        let patch = GamePatch::diff(&source, &target).unwrap();
+        assert!(matches!(patch.apply(&target), Err(PatchNotApplicable(_))));
+        let mut modified = make_file(1, 1);
+        modified.main_code[0] = 0;
+        assert!(matches!(patch.apply(&modified), Err(PatchNotApplicable(_))));
+    }
+
+    #[test]
+    fn oversized_section() {
+        let source = make_file(1, 1);
+        let mut patch = GamePatch::diff(&source, &make_file(2, 1)).unwrap();
+        patch.sections = vec![SectionPatch {
+            section: PatchSection::CodeBank(0),
+            target_len: u32::MAX,
+            deltas: vec![],
+        }];
+        let mut bytes = patch.as_bytes().unwrap();
+        assert!(matches!(
+            GamePatch::from_reader(&mut BufReader::new(&*bytes)),
+            Err(InvalidPatch(_))
+        ));
+        assert!(matches!(patch.apply(&source), Err(InvalidPatch(_))));
+
+        //delta length is checked before reading it
+        patch.sections[0].target_len = CODE_BANK as u32;
+        bytes = patch.as_bytes().unwrap();
+        let len = bytes.len();
+        bytes[len - 4..].copy_from_slice(&1_u32.to_be_bytes());
+        bytes.extend_from_slice(&0_u32.to_be_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(
+            GamePatch::from_reader(&mut BufReader::new(&*bytes)),
+            Err(InvalidPatch(_))
+        ));
+    }
+}
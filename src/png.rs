@@ -0,0 +1,110 @@
+use crate::atlas_file::{get_pixel, AtlasFile};
+use crate::checksum::{adler32, crc32};
+use crate::palette::Palette;
+use maikor_platform::constants::{TILES_PER_ATLAS_ROW, TILE_HEIGHT, TILE_WIDTH};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const MAX_STORED_BLOCK: usize = u16::MAX as usize;
+
+impl AtlasFile {
+    /// Render atlas as an indexed PNG, tiles are laid out in rows of 25 and palette index 0
+    /// is transparent
+    pub fn to_png(&self, palette: &Palette) -> Vec<u8> {
+        let columns = self.len().clamp(1, TILES_PER_ATLAS_ROW);
+        let rows = self.len().div_ceil(TILES_PER_ATLAS_ROW).max(1);
+        let width = columns * TILE_WIDTH;
+        let height = rows * TILE_HEIGHT;
+        let mut pixels = vec![0; width * height];
+        for (i, tile) in self.images().iter().enumerate() {
+            let tile_x = (i % TILES_PER_ATLAS_ROW) * TILE_WIDTH;
+            let tile_y = (i / TILES_PER_ATLAS_ROW) * TILE_HEIGHT;
+            for y in 0..TILE_HEIGHT {
+                for x in 0..TILE_WIDTH {
+                    pixels[(tile_y + y) * width + tile_x + x] = get_pixel(tile, x, y);
+                }
+            }
+        }
+        encode_indexed(width as u32, height as u32, &pixels, palette)
+    }
+}
+
+/// Encode an 8 bit indexed PNG, `pixels` must contain one palette index per pixel
+pub(crate) fn encode_indexed(width: u32, height: u32, pixels: &[u8], palette: &Palette) -> Vec<u8> {
+    let mut output = PNG_SIGNATURE.to_vec();
+
+    let mut header = vec![];
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    //bit depth 8, indexed color, deflate, no filter, no interlace
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+    write_chunk(&mut output, b"IHDR", &header);
+
+    let colors: Vec<u8> = palette.colors.iter().flat_map(|c| c.as_bytes()).collect();
+    write_chunk(&mut output, b"PLTE", &colors);
+    //only index 0 is transparent, all others default to opaque
+    write_chunk(&mut output, b"tRNS", &[0]);
+
+    let mut raw = vec![];
+    for row in pixels.chunks(width as usize) {
+        //filter type: none
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    write_chunk(&mut output, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut output, b"IEND", &[]);
+    output
+}
+
+fn write_chunk(output: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = output.len();
+    output.extend_from_slice(name);
+    output.extend_from_slice(data);
+    let crc = crc32(&output[start..]);
+    output.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wrap `data` in a zlib stream using uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        output.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        output.push(is_final as u8);
+        let len = block.len() as u16;
+        output.extend_from_slice(&len.to_le_bytes());
+        output.extend_from_slice(&(!len).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::atlas_file::ATLAS_SPRITE_SIZE;
+    use crate::palette::Color;
+
+    #[test]
+    fn atlas_png() {
+        let atlas = AtlasFile::new(vec![[0x12; ATLAS_SPRITE_SIZE]; 30]);
+        let png = atlas.to_png(&Palette::new(None, [Color::new(1, 2, 3); 16]));
+        assert_eq!(png[..8], PNG_SIGNATURE);
+        //IHDR width and height
+        assert_eq!(png[16..24], [0, 0, 0, 200, 0, 0, 0, 16]);
+        assert_eq!(png[png.len() - 8..png.len() - 4], *b"IEND");
+    }
+
+    #[test]
+    fn stored_blocks() {
+        let data = vec![7; MAX_STORED_BLOCK + 10];
+        let stream = zlib_stored(&data);
+        assert_eq!(stream.len(), 2 + 5 + MAX_STORED_BLOCK + 5 + 10 + 4);
+        assert_eq!(stream[2], 0);
+        assert_eq!(stream[2 + 5 + MAX_STORED_BLOCK], 1);
+    }
+}
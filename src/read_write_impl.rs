@@ -6,15 +6,13 @@ use std::io::{BufReader, BufWriter, Write};
 use std::path::Path;
 
 fn create_reader<P: AsRef<Path>>(path: P) -> Result<BufReader<File>, GameFileError> {
-    let path = path.as_ref();
-    validate_file(path, true)?;
     let file = File::open(path).map_err(|e| FileAccessError(e, "reading file"))?;
     let reader = BufReader::new(file);
     Ok(reader)
 }
 
 fn create_writer<P: AsRef<Path>>(path: P) -> Result<BufWriter<File>, GameFileError> {
-    let file = File::open(path).map_err(|e| FileAccessError(e, "writing file"))?;
+    let file = File::create(path).map_err(|e| FileAccessError(e, "writing file"))?;
     let writer = BufWriter::new(file);
     Ok(writer)
 }
@@ -49,12 +47,15 @@ pub fn validate_file<P: AsRef<Path>>(path: P, size_check: bool) -> Result<(), Ga
 }
 
 pub trait FileReadable {
+    ///If the file size should be checked against the game file limits
+    const CHECK_SIZE: bool = true;
+
     fn read<P: AsRef<Path>>(path: P) -> Result<Self, GameFileError>
     where
         Self: Sized + Readable,
     {
         let path = path.as_ref();
-        validate_file(path, true)?;
+        validate_file(path, Self::CHECK_SIZE)?;
         let mut reader = create_reader(path)?;
         let header = Self::from_reader(&mut reader)?;
         Ok(header)
@@ -77,9 +78,13 @@ impl FileReadable for GameFile {}
 
 impl GameFile {
     pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), GameFileError> {
+        let bytes = self.as_bytes()?;
         let mut writer = create_writer(path)?;
         writer
-            .write_all(&self.as_bytes()?)
+            .write_all(&bytes)
+            .map_err(|e| FileAccessError(e, "writing file"))?;
+        writer
+            .flush()
             .map_err(|e| FileAccessError(e, "writing file"))?;
         Ok(())
     }
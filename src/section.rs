@@ -0,0 +1,91 @@
+use crate::file_utils::ReaderExt;
+use crate::GameFileError;
+use crate::GameFileError::{FileAccessError, InvalidSection};
+use crate::MAX_SECTIONS_SIZE;
+
+/// Optional sections stored after the atlas banks
+///
+/// Each section is written as `id (u8)`, `length (u32)` and then `length` bytes of data.
+/// Sections are written in id order and readers skip sections they don't understand.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum SectionId {
+    Palettes = 1,
+    Metadata = 2,
+    Icon = 3,
+    Cover = 4,
+    Localizations = 5,
+    BuildInfo = 6,
+}
+
+impl SectionId {
+    pub fn from_u8(value: u8) -> Option<SectionId> {
+        match value {
+            1 => Some(SectionId::Palettes),
+            2 => Some(SectionId::Metadata),
+            3 => Some(SectionId::Icon),
+            4 => Some(SectionId::Cover),
+            5 => Some(SectionId::Localizations),
+            6 => Some(SectionId::BuildInfo),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn sections_too_large(id: u8) -> GameFileError {
+    InvalidSection(
+        id,
+        format!("sections are too large, max is {MAX_SECTIONS_SIZE} bytes"),
+    )
+}
+
+pub(crate) fn write_section(output: &mut Vec<u8>, id: SectionId, data: &[u8]) {
+    output.push(id as u8);
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    output.extend_from_slice(data);
+}
+
+/// Read all sections until the end of the reader
+pub(crate) fn read_sections<R: ReaderExt>(
+    reader: &mut R,
+) -> Result<Vec<(u8, Vec<u8>)>, GameFileError> {
+    let mut sections = vec![];
+    let mut total = 0;
+    while let Some(id) = reader
+        .read_optional_u8()
+        .map_err(|e| FileAccessError(e, "reading section id"))?
+    {
+        let len = reader
+            .read_u32()
+            .map_err(|e| FileAccessError(e, "reading section length"))? as u64;
+        total += len;
+        if total > MAX_SECTIONS_SIZE {
+            return Err(sections_too_large(id));
+        }
+        let data = reader
+            .read_block(len as usize)
+            .map_err(|e| FileAccessError(e, "reading section"))?;
+        sections.push((id, data));
+    }
+    Ok(sections)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn read_write() {
+        let mut bytes = vec![];
+        write_section(&mut bytes, SectionId::Palettes, &[1, 2, 3]);
+        assert_eq!(bytes, [1, 0, 0, 0, 3, 1, 2, 3]);
+        bytes.extend_from_slice(&[99, 0, 0, 0, 1, 5]);
+        let sections = read_sections(&mut BufReader::new(&*bytes)).unwrap();
+        assert_eq!(sections, vec![(1, vec![1, 2, 3]), (99, vec![5])]);
+        assert!(read_sections(&mut BufReader::new(&*vec![]))
+            .unwrap()
+            .is_empty());
+        assert!(read_sections(&mut BufReader::new(&*vec![1, 0, 0, 0, 3, 1])).is_err());
+    }
+}
@@ -0,0 +1,38 @@
+use std::env;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// Empty directory in the temp dir for a test, removed when dropped (even if the test fails)
+pub(crate) struct TestDir(PathBuf);
+
+impl TestDir {
+    /// Create `maikor-NAME-PID`, `name` must be unique across tests as they run in parallel
+    pub(crate) fn new(name: &str) -> TestDir {
+        let dir = env::temp_dir().join(format!("maikor-{}-{}", name, std::process::id()));
+        //left over if an earlier run was killed
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TestDir(dir)
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TestDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}